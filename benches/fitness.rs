@@ -0,0 +1,57 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Benchmark of the key length and key guessing done by `api::run`
+//!
+//! Run with `cargo bench`.
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use xortool_rs::api::{Config, run};
+
+/// Size of the buffer to benchmark with
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Key to encrypt the buffer with
+const KEY: &[u8] = b"xortool-rs benchmark key!";
+
+/// Build a buffer that looks like xored text
+///
+/// # Returns
+///   `BUFFER_SIZE` bytes of repeated text xored with `KEY`
+fn buffer() -> Vec<u8> {
+    let text = b"The quick brown fox jumps over the lazy dog. ";
+    (0..BUFFER_SIZE)
+        .map(|idx| text[idx % text.len()] ^ KEY[idx % KEY.len()])
+        .collect()
+}
+
+/// Time guessing the key with and without the key length given
+///
+/// # Arguments
+///   * `c`: The criterion instance
+fn bench_run(c: &mut Criterion) {
+    let data = buffer();
+    let guess_length = Config {
+        most_frequent_chars: vec![b' '],
+        ..Default::default()
+    };
+    let known_length = Config {
+        key_length: Some(i32::try_from(KEY.len()).unwrap()),
+        ..guess_length.clone()
+    };
+    let mut group = c.benchmark_group("run_1mb");
+    group.sample_size(10);
+    group.bench_function("guess_key_length", |b| {
+        b.iter(|| run(black_box(&data), black_box(&guess_length)));
+    });
+    group.bench_function("known_key_length", |b| {
+        b.iter(|| run(black_box(&data), black_box(&known_length)));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_run);
+criterion_main!(benches);
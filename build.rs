@@ -0,0 +1,26 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Build script recording the target and enabled features for `--version`
+use std::env;
+
+fn main() {
+    let target = env::var("TARGET").unwrap_or_else(|_| String::from("unknown"));
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(name, _)| {
+            name.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+    let features = if features.is_empty() {
+        String::from("none")
+    } else {
+        features.join(", ")
+    };
+    println!("cargo::rustc-env=XORTOOL_TARGET={target}");
+    println!("cargo::rustc-env=XORTOOL_FEATURES={features}");
+    println!("cargo::rerun-if-changed=build.rs");
+}
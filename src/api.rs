@@ -0,0 +1,276 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Library interface for running the xortool analysis without printing or writing files
+use crate::{
+    args::{DEFAULT_MAX_CANDIDATE_KEYS, Parameters},
+    charset::PREDEFINED_CHARSETS,
+    error::XorError,
+    tool_main::{guess_keys_for_length, key_confidence, score_plaintext, select_key_lengths},
+};
+
+/// Settings for an analysis run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Length of the key, or `None` to guess it
+    pub key_length: Option<i32>,
+    /// Maximum key length to consider when guessing the key length
+    pub max_key_length: i32,
+    /// Characters to try as the most frequent character of the plaintext
+    pub most_frequent_chars: Vec<u8>,
+    /// Bytes that are considered valid in the plaintext
+    pub text_charset: Vec<u8>,
+    /// Percentage of valid characters a plaintext needs to pass
+    pub threshold: i32,
+    /// Known plaintext that has to appear in the decrypted output
+    pub known_plain: Option<Vec<u8>>,
+    /// Maximum number of keys to generate for each most frequent character
+    /// when key bytes are tied
+    pub max_candidate_keys: usize,
+}
+
+impl Default for Config {
+    /// Guess the key length up to 65 bytes, score plaintexts against the
+    /// printable charset, and keep up to 1000 keys per character like the
+    /// command line tool. `most_frequent_chars` is empty, and must be set.
+    fn default() -> Self {
+        Self {
+            key_length: None,
+            max_key_length: 65,
+            most_frequent_chars: Vec::new(),
+            text_charset: PREDEFINED_CHARSETS["printable"].as_bytes().to_vec(),
+            threshold: 95,
+            known_plain: None,
+            max_candidate_keys: DEFAULT_MAX_CANDIDATE_KEYS,
+        }
+    }
+}
+
+/// A guessed key and how well it decrypts the data
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyCandidate {
+    /// The bytes of the key
+    pub key: Vec<u8>,
+    /// The most frequent character the key was found with
+    pub char_used: u8,
+    /// Fraction (0 to 1) of decrypted bytes that are in the charset
+    pub percentage_valid: f64,
+    /// Whether `percentage_valid` is above the threshold
+    pub passes_threshold: bool,
+}
+
+/// Results of an analysis run
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisReport {
+    /// Candidate key lengths and their fitness, best first. Empty if the
+    /// key length was given in the config.
+    pub key_length_candidates: Vec<(i32, f64)>,
+    /// The key length that was used to guess keys
+    pub key_length: i32,
+    /// The guessed keys. Keys whose output doesn't contain the known
+    /// plaintext are left out.
+    pub keys: Vec<KeyCandidate>,
+    /// For each key offset, the count of the most common byte as a fraction
+    /// (0 to 1) of the bytes at that offset. Key bytes at offsets with a low
+    /// fraction are less certain.
+    pub key_confidence: Vec<f64>,
+}
+
+impl From<&Config> for Parameters {
+    fn from(cfg: &Config) -> Self {
+        Self {
+            max_key_length: Some(cfg.max_key_length),
+            key_lengths: cfg.key_length.into_iter().collect(),
+            text_charset: cfg.text_charset.clone(),
+            threshold: Some(cfg.threshold),
+            known_plain: cfg.known_plain.clone(),
+            max_candidate_keys: cfg.max_candidate_keys,
+            quiet: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// Check that a config describes an analysis that can be run
+///
+/// # Arguments
+///   * `cfg`: Settings for the analysis
+///
+/// # Errors
+///   Returns `XorError::ArgParser` for settings the command line tool would
+///   reject as arguments, and `XorError::Analysis` if no most frequent
+///   characters were given.
+fn validate(cfg: &Config) -> Result<(), XorError> {
+    let invalid = if cfg.key_length.is_some_and(|key_length| key_length < 1) {
+        Some("Key length must be at least 1")
+    } else if cfg.max_key_length < 1 {
+        Some("Maximum key length must be at least 1")
+    } else if cfg.known_plain.as_ref().is_some_and(Vec::is_empty) {
+        Some("Known plaintext can't be empty")
+    } else if cfg.max_candidate_keys < 1 {
+        Some("Maximum candidate keys must be at least 1")
+    } else {
+        None
+    };
+    if let Some(msg) = invalid {
+        return Err(XorError::ArgParser {
+            msg: msg.to_owned(),
+        });
+    }
+    if cfg.most_frequent_chars.is_empty() {
+        return Err(XorError::Analysis {
+            msg: String::from("Most possible char is needed to guess the key!"),
+        });
+    }
+    Ok(())
+}
+
+/// Run the full analysis on some encrypted data
+///
+/// This goes through the same key length selection, key guessing and
+/// scoring steps as the command line tool, without printing or writing files.
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `cfg`: Settings for the analysis
+///
+/// # Returns
+///   A report of the candidate key lengths and guessed keys.
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if the config is invalid,
+///   `XorError::Analysis` if no most frequent characters were given or no
+///   key length could be guessed.
+pub fn run(text: &[u8], cfg: &Config) -> Result<AnalysisReport, XorError> {
+    validate(cfg)?;
+    let mut param = Parameters::from(cfg);
+
+    let (key_lengths, key_length_candidates) = select_key_lengths(text, &param)?;
+    let key_length = key_lengths[0];
+    param.known_key_length = Some(key_length);
+
+    let (probable_keys, key_char_used) =
+        guess_keys_for_length(text, text, &cfg.most_frequent_chars, &param)?;
+    let keys = probable_keys
+        .into_iter()
+        .filter_map(|key| {
+            let scored = score_plaintext(text, &key, &param)?;
+            Some(KeyCandidate {
+                char_used: key_char_used[&key],
+                key,
+                percentage_valid: scored.percentage_valid,
+                passes_threshold: scored.passes_threshold,
+            })
+        })
+        .collect();
+
+    Ok(AnalysisReport {
+        key_length_candidates,
+        key_length,
+        keys,
+        key_confidence: key_confidence(text, key_length),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::routine::dexor;
+
+    #[test]
+    fn test_run_guesses_key() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let cfg = Config {
+            most_frequent_chars: vec![b' '],
+            ..Default::default()
+        };
+        let report = run(&dexor(&plaintext, b"secret!"), &cfg).unwrap();
+        assert_eq!(report.key_length, 7);
+        assert_eq!(report.key_length_candidates[0].0, 7);
+        assert_eq!(report.keys.len(), 1);
+        assert_eq!(report.keys[0].key, b"secret!");
+        assert_eq!(report.keys[0].char_used, b' ');
+        assert!(report.keys[0].passes_threshold);
+    }
+
+    #[test]
+    fn test_run_known_key_length() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let cfg = Config {
+            key_length: Some(3),
+            most_frequent_chars: vec![b' '],
+            known_plain: Some(b"harbour".to_vec()),
+            ..Default::default()
+        };
+        let report = run(&dexor(&plaintext, b"k3y"), &cfg).unwrap();
+        assert!(report.key_length_candidates.is_empty());
+        assert_eq!(report.key_length, 3);
+        assert_eq!(report.keys[0].key, b"k3y");
+        assert_eq!(report.key_confidence.len(), 3);
+        assert!(report.key_confidence.iter().all(|&c| c > 0.1 && c <= 1.0));
+    }
+
+    #[test]
+    fn test_run_known_plain_filters_keys() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let cfg = Config {
+            key_length: Some(3),
+            most_frequent_chars: vec![b' '],
+            known_plain: Some(b"not in the text".to_vec()),
+            ..Default::default()
+        };
+        let report = run(&dexor(&plaintext, b"k3y"), &cfg).unwrap();
+        assert!(report.keys.is_empty());
+    }
+
+    #[test]
+    fn test_run_rejects_invalid_config() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let ciphertext = dexor(&plaintext, b"k3y");
+        let valid = Config {
+            most_frequent_chars: vec![b' '],
+            ..Default::default()
+        };
+        for cfg in [
+            Config {
+                known_plain: Some(Vec::new()),
+                ..valid.clone()
+            },
+            Config {
+                key_length: Some(0),
+                ..valid.clone()
+            },
+            Config {
+                key_length: Some(-3),
+                ..valid.clone()
+            },
+            Config {
+                max_key_length: 0,
+                ..valid.clone()
+            },
+            Config {
+                max_candidate_keys: 0,
+                ..valid.clone()
+            },
+        ] {
+            assert!(matches!(
+                run(&ciphertext, &cfg),
+                Err(XorError::ArgParser { .. })
+            ));
+        }
+        assert_eq!(valid.max_candidate_keys, 1000);
+        assert_eq!(Parameters::from(&valid).max_candidate_keys, 1000);
+    }
+
+    #[test]
+    fn test_run_needs_most_frequent_chars() {
+        assert!(matches!(
+            run(b"Hello World!", &Config::default()),
+            Err(XorError::Analysis { .. })
+        ));
+    }
+}
@@ -4,9 +4,25 @@
 * file, You can obtain one at https: //mozilla.org/MPL/2.0/.
 */
 //! Command line argument parsing utilities.
-use clap::Parser;
+use std::time::{Duration, Instant};
 
-use crate::{charset::get_charset, error::XorError};
+use clap::{Parser, ValueEnum, builder::RangedU64ValueParser};
+use clap_complete::Shell;
+
+use crate::{
+    charset::{PREDEFINED_CHARSETS, get_charset_bytes},
+    error::XorError,
+    tool_main::{BUILD_INFO, VERSION},
+};
+
+/// File name that reads from standard input, and the default input
+const STDIN_FILENAME: &str = "-";
+
+/// Charset plaintexts are scored against by default
+const DEFAULT_CHARSET: &str = "printable";
+
+/// Default maximum number of keys to generate for each most frequent character
+pub const DEFAULT_MAX_CANDIDATE_KEYS: usize = 1000;
 
 /// Parse `most_frequent_char` argument into a byte
 ///
@@ -18,12 +34,18 @@ use crate::{charset::get_charset, error::XorError};
 ///
 /// # Errors
 ///   Returns an error of the supplied argument text is empty
-///   or if the supplied argument is not a single character, or a hex
-///   value (i.e. prefixed with `0x` or `\\x`)
+///   or if the supplied argument is not a single character, a hex
+///   value (i.e. prefixed with `0x` or `\\x`), or a decimal value
+///   (i.e. prefixed with `d:`) between 0 and 255
 fn parse_most_frequent(mut arg: &str) -> Result<u8, XorError> {
     if arg.len() == 1 {
         return Ok(arg.bytes().collect::<Vec<u8>>()[0]);
     }
+    if let Some(decimal) = arg.strip_prefix("d:") {
+        return decimal.parse().map_err(|e| XorError::ArgParser {
+            msg: format!("Decimal char must be between 0 and 255, got '{decimal}' ({e})"),
+        });
+    }
     if arg.starts_with("0x") {
         arg = arg.strip_prefix("0x").unwrap();
     }
@@ -40,7 +62,76 @@ fn parse_most_frequent(mut arg: &str) -> Result<u8, XorError> {
             msg: "Char can only be a char letter or hex".to_owned(),
         });
     }
-    Ok(u8::from_str_radix(arg, 16).unwrap())
+    u8::from_str_radix(arg, 16).map_err(|e| XorError::ArgParser {
+        msg: format!("Invalid hex char '{arg}' ({e})"),
+    })
+}
+
+/// Parse the `csv_delimiter` argument into a byte
+///
+/// # Arguments
+///   * `arg`: The argument to parse
+///
+/// # Returns
+///   The delimiter byte
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if the argument is not a single byte, or
+///   is a quote or line break, which can't separate CSV fields
+fn parse_csv_delimiter(arg: &str) -> Result<u8, XorError> {
+    match arg.as_bytes() {
+        [b'"' | b'\r' | b'\n'] => Err(XorError::ArgParser {
+            msg: format!("CSV delimiter can't be {}", arg.escape_default()),
+        }),
+        &[delimiter] => Ok(delimiter),
+        _ => Err(XorError::ArgParser {
+            msg: format!("CSV delimiter must be a single byte, got '{arg}'"),
+        }),
+    }
+}
+
+/// Parse the `timeout` argument into a duration
+///
+/// # Arguments
+///   * `arg`: The number of seconds, which may have a fraction
+///
+/// # Returns
+///   The timeout
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if the argument is not a number of
+///   seconds that is zero or more
+fn parse_timeout(arg: &str) -> Result<Duration, XorError> {
+    let seconds: f64 = arg.parse().map_err(|e| XorError::ArgParser {
+        msg: format!("Timeout must be a number of seconds, got '{arg}' ({e})"),
+    })?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| XorError::ArgParser {
+        msg: format!("Timeout must be zero or more seconds, got '{arg}' ({e})"),
+    })
+}
+
+/// Parse the `prefer_boost` argument into a fitness factor
+///
+/// # Arguments
+///   * `arg`: The factor to parse
+///
+/// # Returns
+///   The factor to multiply the fitness of the preferred key lengths by
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if the argument is not a finite number
+///   of at least 1, since a smaller factor would disfavor the length
+fn parse_prefer_boost(arg: &str) -> Result<f64, XorError> {
+    let boost: f64 = arg.parse().map_err(|e| XorError::ArgParser {
+        msg: format!("Preferred length boost must be a number, got '{arg}' ({e})"),
+    })?;
+    if boost.is_finite() && boost >= 1.0 {
+        Ok(boost)
+    } else {
+        Err(XorError::ArgParser {
+            msg: format!("Preferred length boost must be at least 1, got '{arg}'"),
+        })
+    }
 }
 
 /// Convert a string to a vector of bytes
@@ -63,26 +154,156 @@ fn str_to_bytes(arg: &str) -> Result<Vec<u8>, XorError> {
     }
 }
 
+/// Parse a partially known key, with `?` marking the unknown bytes
+///
+/// Bytes can be given as `\xAB` escapes, and `\?` or `\\` give a literal
+/// `?` or `\`.
+///
+/// # Arguments
+///   * `arg`: The seed key to parse
+///
+/// # Returns
+///   The key bytes, with `None` for unknown positions
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if the seed is empty or has a bad escape
+fn parse_seed_key(arg: &str) -> Result<Vec<Option<u8>>, XorError> {
+    if arg.is_empty() {
+        return Err(XorError::ArgParser {
+            msg: "Empty String".to_owned(),
+        });
+    }
+    let bytes = arg.as_bytes();
+    let mut seed = Vec::new();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match (bytes[idx], bytes.get(idx + 1)) {
+            (b'?', _) => seed.push(None),
+            (b'\\', Some(&escaped @ (b'?' | b'\\'))) => {
+                seed.push(Some(escaped));
+                idx += 1;
+            }
+            (b'\\', Some(b'x')) => {
+                let hex = arg.get(idx + 2..idx + 4).unwrap_or_default();
+                let byte = u8::from_str_radix(hex, 16).map_err(|e| XorError::ArgParser {
+                    msg: format!("Invalid hex escape '\\x{hex}' in seed key ({e})"),
+                })?;
+                seed.push(Some(byte));
+                idx += 3;
+            }
+            (b'\\', _) => {
+                return Err(XorError::ArgParser {
+                    msg: String::from("Seed key escapes must be \\xAB, \\? or \\\\"),
+                });
+            }
+            (byte, _) => seed.push(Some(byte)),
+        }
+        idx += 1;
+    }
+    Ok(seed)
+}
+
+/// Parse the extension for the plaintext output files
+///
+/// # Arguments
+///   * `arg`: The argument to parse
+///
+/// # Returns
+///   The extension, without a leading `.`
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if the extension is empty or contains a
+///   path separator
+fn parse_out_ext(arg: &str) -> Result<String, XorError> {
+    let ext = arg.strip_prefix('.').unwrap_or(arg);
+    if ext.is_empty() || ext.contains(['/', '\\']) {
+        return Err(XorError::ArgParser {
+            msg: format!("Invalid output file extension '{arg}'"),
+        });
+    }
+    Ok(ext.to_owned())
+}
+
+/// Formats that guessed keys can be displayed in
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// Printable string with non-printable bytes escaped (e.g. `\xde`)
+    #[default]
+    Escaped,
+    /// Lowercase hex with no separators
+    Hex,
+    /// The raw bytes of the key
+    Raw,
+}
+
+/// Byte order of the 16-bit characters analyzed with `--wide`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endian {
+    /// Little-endian (e.g. UTF-16LE), the high byte comes second
+    Le,
+    /// Big-endian (e.g. UTF-16BE), the high byte comes first
+    Be,
+}
+
+/// Language whose byte string literals escaped keys are written as
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum KeyLang {
+    /// Python byte strings, e.g. `b'k\x00y'`
+    #[default]
+    Python,
+    /// Rust byte strings, e.g. `b"k\x00y"`
+    Rust,
+    /// C strings with every byte hex escaped, e.g. `"\x6b\x00\x79"`
+    C,
+}
+
+/// Line ending to use for printed output and output files
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// CRLF on Windows, LF everywhere else
+    #[default]
+    Auto,
+    /// Always use LF (`\n`)
+    Lf,
+    /// Always use CRLF (`\r\n`)
+    Crlf,
+}
+
+/// How key length fitness is normalized before comparing key lengths
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum Score {
+    /// Divide by `max_key_length + key_length^1.5`, as the Python xortool does
+    #[default]
+    Weighted,
+    /// Divide by the number of comparisons made, giving a per-byte agreement rate
+    Agreement,
+    /// Like `weighted`, but only count columns whose most common byte gives a
+    /// printable key byte if the plaintext byte is a space
+    PrintableWeighted,
+}
+
 /// Structure holding the parsed command line arguments
 #[expect(
     clippy::struct_excessive_bools,
     reason = "This structure holds CLI args, lots of bools are expected as they are for flags."
 )]
-#[derive(Parser, Default)]
+#[derive(Parser, Debug)]
 #[command(
-    version,
+    version = VERSION,
+    long_version = BUILD_INFO,
     about = "A tool to do some xor analysis:\n- Guess the key length (based on count of equal chars)\n- Guess the key (based on knowledge of most frequent char)",
     after_help = "
 
 Notes:
 Text character set:
-    * Pre-defined sets: printable, base32, base64
+    * Pre-defined sets: printable, base16, base32, base64, ascii, alnum
     * Custom sets:
     - a: lowercase chars
     - A: uppercase chars
     - 1: digits
     - !: special chars
     - *: printable chars
+    * Remove chars from a set with -, e.g. 'printable-\\t\\n\\r ' (printable without whitespace)
 
 Examples:
 xortool file.bin
@@ -90,6 +311,7 @@ xortool -l 11 -c 20 file.bin
 xortool -x -c ' ' file.hex
 xortool -b -f -l 23 -t base64 message.enc
 xortool -r 80 -p \"flag{\" -c ' ' message.enc
+xortool -c 20 -- -file-starting-with-dash.bin
 "
 )]
 pub struct Parameters {
@@ -105,55 +327,176 @@ pub struct Parameters {
     )]
     pub brute_printable: bool,
 
-    /// Name of the file to read in from
-    #[arg(default_value = "-")]
-    pub filename: String,
+    /// Names of the files to read in from, concatenated in order
+    ///
+    /// Names after a `--` are always taken as file names, even if they start with `-`.
+    #[arg(default_value = STDIN_FILENAME, value_name = "FILENAME")]
+    pub filenames: Vec<String>,
 
     /// Whether or not to filter outputs based on the charset.
     #[arg(short, long, help = "filter outputs based on the charset")]
     pub filter_output: bool,
 
+    /// Extension of the plaintext output files
+    #[arg(
+        long,
+        value_name = "EXT",
+        value_parser = parse_out_ext,
+        help = "Extension for the plaintext output files, e.g. png [default: out]"
+    )]
+    pub out_ext: Option<String>,
+
+    /// Number of the most valid plaintexts to print
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Print the N plaintexts with the most valid characters, with non-printable bytes escaped"
+    )]
+    pub show_best: Option<usize>,
+
+    /// Whether to print how well the best plaintext fits each predefined charset
+    #[arg(
+        long,
+        help = "Print the percentage of the best plaintext's bytes in each of the printable, base32, base64 and ascii charsets"
+    )]
+    pub charset_report: bool,
+
     /// Whether or not the input is a hex-encoded string.
     #[arg(short = 'x', long = "hex", help = "input is hex-encoded str")]
     pub input_is_hex: bool,
 
-    /// Optional known length of the key
+    /// Whether or not the input is a hex dump with offsets and an ASCII gutter.
+    #[arg(
+        long,
+        conflicts_with = "input_is_hex",
+        help = "input is a `hexdump -C` or `xxd` style dump"
+    )]
+    pub hexdump: bool,
+
+    /// Whether or not the input is text with `\xNN` and other backslash escapes
+    #[arg(
+        long,
+        conflicts_with_all = ["input_is_hex", "hexdump"],
+        help = "input is a string with \\xNN and other backslash escapes, as copied from a debugger"
+    )]
+    pub escaped: bool,
+
+    /// Whether or not the input is gzip/zlib compressed.
+    #[cfg(feature = "gzip")]
+    #[arg(long, help = "decompress gzip/zlib input before analysis")]
+    pub gzip_input: bool,
+
+    /// Known lengths of the key to try
     #[arg(
         short = 'l',
         long = "key-length",
         value_name = "LEN",
-        help = "Length of the key"
+        value_delimiter = ',',
+        value_parser = clap::value_parser!(i32).range(1..),
+        help = "Length of the key (or a comma-separated list of lengths to try)"
+    )]
+    pub key_lengths: Vec<i32>,
+
+    /// Length of the key as a percentage of the input length
+    #[arg(
+        long,
+        value_name = "P",
+        conflicts_with_all = ["key_lengths", "seed_key"],
+        help = "Use a key length of P percent of the input length instead of guessing it"
+    )]
+    pub keylen_pct: Option<f64>,
+
+    /// Number of top key length candidates to compare by their best plaintext
+    #[arg(
+        long,
+        value_name = "K",
+        conflicts_with_all = ["key_lengths", "seed_key", "keylen_pct"],
+        help = "Guess the keys for each of the K most probable key lengths, print the best percentage of valid characters reached with each, and exit"
     )]
+    pub scan_lengths: Option<usize>,
+
+    /// Key length whose fitness (and that of its multiples) is boosted
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(i32).range(1..),
+        conflicts_with_all = ["key_lengths", "keylen_pct", "scan_lengths"],
+        help = "Favor key length N and its multiples when guessing the key length, without forcing it"
+    )]
+    pub prefer_length: Option<i32>,
+
+    /// Factor the fitness of the preferred key lengths is multiplied by
+    #[arg(
+        long,
+        value_name = "F",
+        value_parser = parse_prefer_boost,
+        requires = "prefer_length",
+        help = "Multiply the fitness of the --prefer-length lengths by F [default: 1.1]"
+    )]
+    pub prefer_boost: Option<f64>,
+
+    /// Length of the key currently being used for guessing keys
+    #[arg(skip)]
     pub known_key_length: Option<i32>,
 
+    /// Whether or not to prefer the most common divisor over the max-fitness key length
+    #[arg(
+        long,
+        help = "Prefer the most common divisor as the key length when it has more fitness support"
+    )]
+    pub use_divisor: bool,
+
+    /// Whether to print all of the most common divisors instead of the smallest 3
+    #[arg(
+        long,
+        help = "Print every most common divisor of the key length candidates, not just the smallest 3"
+    )]
+    pub all_divisors: bool,
+
     /// Maximum key length to probe.
     #[arg(
         short = 'm',
         long = "max-keylen",
         value_name = "MAXLEN",
-        help = "Maximum key length to probe [default: 65]",
-        default_value = "65"
+        help = "Maximum key length to probe [default: 65, or a quarter of the input length if that is smaller]"
     )]
     pub max_key_length: Option<i32>,
 
+    /// Only probe key lengths that are a multiple of this
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(i32).range(1..),
+        help = "Only probe key lengths that are a multiple of N"
+    )]
+    pub multiple_of: Option<i32>,
+
     /// Known most frequent character in the plaintext
     #[arg(
         short = 'c',
         long = "char",
         value_name = "CHAR",
         value_parser = parse_most_frequent,
-        help = "Most frequent char (one char or hex code)"
+        help = "Most frequent char (one char, hex code, or decimal as d:N)"
     )]
     pub most_frequent_char: Option<u8>,
 
+    /// Assume the most frequent plaintext character is a space
+    #[arg(
+        long,
+        conflicts_with_all = ["most_frequent_char", "brute_chars", "brute_printable"],
+        help = "Assume the most common byte of each key offset is a space in the plaintext"
+    )]
+    pub auto_char: bool,
+
     /// Target text character set
     #[arg(
         short = 't',
         long = "text-charset",
         value_name = "CHARSET",
         help = "Target text character set [default: printable]",
-        value_parser = get_charset,
-        default_value="printable"
+        value_parser = get_charset_bytes,
+        default_value = DEFAULT_CHARSET
     )]
     #[expect(
         clippy::absolute_paths,
@@ -161,6 +504,32 @@ pub struct Parameters {
     )]
     pub text_charset: std::vec::Vec<u8>,
 
+    /// Whether to count bytes of valid multi-byte UTF-8 characters as valid
+    #[arg(
+        long,
+        help = "Score plaintexts as UTF-8: bytes of valid multi-byte characters count as valid, and ASCII bytes must be in the charset"
+    )]
+    pub utf8_score: bool,
+
+    /// Whether ASCII letters are valid in either case
+    #[arg(
+        long,
+        help = "Count an ASCII letter as valid if either its lower or upper case is in the charset"
+    )]
+    pub charset_ignore_case: bool,
+
+    /// Byte order of 16-bit characters whose high byte is zero
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ENDIAN",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "le",
+        help = "Treat the plaintext as 16-bit characters (e.g. UTF-16 text) with zero high bytes when guessing keys [default: le]"
+    )]
+    pub wide: Option<Endian>,
+
     /// Known plaintext to use for decoding
     #[arg(
         short = 'p',
@@ -175,6 +544,19 @@ pub struct Parameters {
     )]
     pub known_plain: Option<std::vec::Vec<u8>>,
 
+    /// Partially known key, with `None` for the bytes to guess
+    #[arg(
+        long,
+        value_name = "KEY",
+        value_parser = parse_seed_key,
+        help = "Partially known key, with ? for unknown bytes (e.g. 'se??et' or '\\x73\\x65??')"
+    )]
+    #[expect(
+        clippy::absolute_paths,
+        reason = "This needs to be fully qualified to work properly. See https://github.com/clap-rs/clap/issues/4481#issuecomment-1314475143"
+    )]
+    pub seed_key: Option<std::vec::Vec<Option<u8>>>,
+
     /// Threshold validity percentage (default: 95)
     #[arg(
         short = 'r',
@@ -183,4 +565,596 @@ pub struct Parameters {
         help = "Threshold validity percentage [default: 95]"
     )]
     pub threshold: Option<i32>,
+
+    /// Number of most frequent bytes to print instead of analyzing the input
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "10",
+        help = "Print the N most frequent bytes of the input and exit [default: 10]"
+    )]
+    pub histogram: Option<usize>,
+
+    /// Whether to stop after the key length analysis
+    #[arg(
+        long,
+        conflicts_with_all = ["key_lengths", "seed_key"],
+        help = "Print the key length analysis and exit, without guessing keys"
+    )]
+    pub only_key_length: bool,
+
+    /// Whether or not to only print the final summary
+    #[arg(
+        short,
+        long,
+        help = "Only print the final summary, not the key lengths, divisors, or keys"
+    )]
+    pub quiet: bool,
+
+    /// Keep previous results and write into a new numbered subdirectory
+    #[arg(
+        long,
+        help = "Keep the existing output directory and write into a new xortool_out/run_N subdirectory"
+    )]
+    pub keep_output: bool,
+
+    /// Whether to skip chars whose keys could not be guessed
+    #[arg(
+        long,
+        help = "Skip most frequent chars whose keys can't be guessed instead of stopping, and summarize them"
+    )]
+    pub keep_going: bool,
+
+    /// Whether to append to the CSVs instead of starting them over
+    #[arg(
+        long,
+        help = "Keep the output directory and append to its CSVs, with a source column naming the input"
+    )]
+    pub append_csv: bool,
+
+    /// Field separator of the CSVs
+    #[arg(
+        long,
+        value_name = "CHAR",
+        value_parser = parse_csv_delimiter,
+        help = "Separate the CSV fields with CHAR instead of `;`, quoting fields that hold it"
+    )]
+    pub csv_delimiter: Option<u8>,
+
+    /// Number of bytes at the start of each plaintext that are checked for being printable
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "16",
+        help = "Check whether the first N bytes of each plaintext are printable, adding a column to the CSV and a count to the summary [default: 16]"
+    )]
+    pub first_n_bytes_printable: Option<usize>,
+
+    /// Name to label standard input with in the output
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Name for standard input in the CSV source column and the report. Does not change what is read"
+    )]
+    pub stdin_name: Option<String>,
+
+    /// Whether to print how long each phase of the analysis took
+    #[arg(
+        long,
+        help = "Print how long key length guessing, key guessing and plaintext production took to stderr"
+    )]
+    pub benchmark: bool,
+
+    /// Whether to print the resolved parameters before the analysis
+    #[arg(
+        long,
+        help = "Print the resolved parameters (charset bytes, key length, threshold, ...) before the analysis"
+    )]
+    pub echo_params: bool,
+
+    /// Longest time the analysis may take
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = parse_timeout,
+        help = "Give up with a summary of how far the analysis got if it takes longer than SECONDS"
+    )]
+    pub timeout: Option<Duration>,
+
+    /// When the analysis has to be done by, set from `timeout` once it starts
+    #[arg(skip)]
+    pub deadline: Option<Instant>,
+
+    /// Tar archive to write the output into instead of the output directory
+    #[cfg(feature = "tar")]
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Write the plaintexts and CSVs into a tar archive instead of the output directory"
+    )]
+    pub tar: Option<String>,
+
+    /// Whether or not to write a report of the analysis into the output directory
+    #[arg(
+        long,
+        help = "Write a report.txt summarizing the analysis into the output directory"
+    )]
+    pub report: bool,
+
+    /// File to write the key length fitness table to
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Write all candidate key lengths with their fitness and probability to FILE as TSV"
+    )]
+    pub fitness_out: Option<String>,
+
+    /// Shell to print a completion script for
+    #[arg(long, value_name = "SHELL", hide = true)]
+    pub completions: Option<Shell>,
+
+    /// Whether to print the available charsets and exit
+    #[arg(long, help = "List the charsets that -t accepts and exit")]
+    pub list_charsets: bool,
+
+    /// Exit with a different code for each kind of error
+    #[arg(
+        long,
+        help = "Exit with 2 for argument errors, 3 for file errors, 4 for analysis failures, and 5 for undecodable input, instead of always 1"
+    )]
+    pub detailed_exit_codes: bool,
+
+    /// Line ending to use for printed output and output files
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "Line ending to use for output. `auto` uses CRLF on Windows and LF elsewhere"
+    )]
+    pub line_ending: LineEnding,
+
+    /// Print the entropy of each column for the key length in use
+    #[arg(
+        long,
+        help = "Print the entropy of each key offset's column for the key length in use"
+    )]
+    pub column_entropy: bool,
+
+    /// Whether or not to print the bytes of each key offset's column
+    #[arg(
+        long,
+        help = "Print the bytes (hex) of each key offset's column for the key length in use"
+    )]
+    pub dump_columns: bool,
+
+    /// Only keep guessed keys that are entirely printable
+    #[arg(
+        long,
+        help = "Only keep guessed keys made up entirely of printable characters"
+    )]
+    pub printable_key: bool,
+
+    /// Prune non-printable key bytes before expanding the key combinations
+    #[arg(
+        long,
+        help = "Only guess printable key bytes, pruning the others before the keys are expanded"
+    )]
+    pub guess_only_printable_keys: bool,
+
+    /// Drop the guessed key made up entirely of zero bytes
+    #[arg(
+        long,
+        visible_alias = "null-key-filter",
+        help = "Drop the all-zero key, which leaves the ciphertext unchanged"
+    )]
+    pub drop_null_key: bool,
+
+    /// Order keys and output files by how valid their plaintext is
+    #[arg(
+        long,
+        help = "Order keys and output files so the most valid plaintext is first (0.out)"
+    )]
+    pub sort_by_validity: bool,
+
+    /// Maximum number of keys to generate for each most frequent character
+    #[arg(
+        long,
+        visible_alias = "max-candidates-per-char",
+        value_name = "N",
+        default_value_t = DEFAULT_MAX_CANDIDATE_KEYS,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        help = "Maximum number of keys to generate for each most frequent char when key bytes are tied"
+    )]
+    pub max_candidate_keys: usize,
+
+    /// Maximum number of keys to keep across all most frequent characters
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        help = "Stop trying more most frequent chars once N keys are found in total (bounds -b runs)"
+    )]
+    pub max_total_candidate_keys: Option<usize>,
+
+    /// Whether to generate every combination of tied key bytes
+    #[arg(
+        long,
+        conflicts_with = "max_candidate_keys",
+        help = "Generate every key when key bytes are tied, however many there are (may use a lot of memory)"
+    )]
+    pub unlimited_candidate_keys: bool,
+
+    /// Print how many key bytes are tied at each offset
+    #[arg(
+        long,
+        help = "Print how many bytes are tied for most common at each key offset"
+    )]
+    pub show_ambiguity: bool,
+
+    /// Print how strongly each key byte was determined
+    #[arg(
+        long,
+        help = "Print the fraction of each key offset's column that is its most common byte"
+    )]
+    pub key_confidence: bool,
+
+    /// Indices of two guessed keys to compare the decryptions of
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["A", "B"],
+        help = "Print the offsets where the decryptions with keys A and B (as numbered in the output files) differ"
+    )]
+    pub compare: Option<Vec<usize>>,
+
+    /// Report the best candidate when no plaintext reaches the threshold
+    #[arg(
+        long,
+        help = "When no plaintext reaches the threshold, print the best score and its file index"
+    )]
+    pub retain_below_threshold: bool,
+
+    /// Number of bytes at the start of the input to leave out of the analysis
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        help = "Leave the first N bytes out of the analysis. The key is still applied to the whole file"
+    )]
+    pub skip_head: usize,
+
+    /// Number of bytes at the end of the input to leave out of the analysis
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        help = "Leave the last N bytes out of the analysis. The key is still applied to the whole file"
+    )]
+    pub skip_tail: usize,
+
+    /// Offset of the first byte of the input to use
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Only use the input from byte N on. Unlike --skip-head, the rest of the input is not decrypted either"
+    )]
+    pub input_offset: Option<usize>,
+
+    /// Number of bytes of the input to use
+    #[arg(
+        long,
+        value_name = "M",
+        help = "Only use M bytes of the input, starting at --input-offset. Unlike --skip-tail, the rest of the input is not decrypted either"
+    )]
+    pub input_length: Option<usize>,
+
+    /// Only guess the key length from the first N bytes of the input
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Guess the key length from only the first N bytes. Faster for huge files, but too small a sample makes the guess less accurate. The key is still applied to the whole file"
+    )]
+    pub sample_bytes: Option<usize>,
+
+    /// How to normalize the fitness of each key length
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "How to score key lengths. `agreement` makes fitness comparable across key lengths, `printable-weighted` favors printable keys"
+    )]
+    pub score: Score,
+
+    /// Guess the key length from the autocorrelation of the ciphertext
+    #[arg(
+        long,
+        visible_alias = "repeat-key-detect",
+        help = "Guess the key length from how often bytes repeat at each shift, instead of counting equal chars"
+    )]
+    pub autocorr: bool,
+
+    /// Format to display guessed keys in
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        default_value_t,
+        help = "How to display guessed keys"
+    )]
+    pub key_format: KeyFormat,
+
+    /// Whether to print guessed keys both escaped and in hex
+    #[arg(
+        long,
+        conflicts_with = "key_format",
+        help = "Print each guessed key both escaped and in hex"
+    )]
+    pub key_both: bool,
+
+    /// Whether to note guessed keys that repeat a shorter key
+    #[arg(
+        long,
+        help = "Note when a guessed key is a shorter key repeated, and print the shorter key"
+    )]
+    pub key_period: bool,
+
+    /// Whether to list the guessed keys as raw bytes separated by NUL bytes
+    #[arg(
+        long,
+        visible_alias = "null-separated",
+        conflicts_with_all = ["key_format", "key_both", "key_period"],
+        help = "List all guessed keys as raw bytes, each followed by a NUL byte and without colors, for scripts"
+    )]
+    pub print0: bool,
+
+    /// Language of the literals escaped keys are written as
+    #[arg(
+        long,
+        value_enum,
+        value_name = "LANG",
+        default_value_t,
+        help = "Language of the byte string literals that escaped keys are written as"
+    )]
+    pub key_lang: KeyLang,
+
+    /// Whether or not to print the Shannon entropy of the ciphertext
+    #[arg(long, help = "Print the Shannon entropy of the ciphertext")]
+    pub entropy: bool,
+
+    /// Whether or not to suppress the warning about high entropy input
+    #[arg(
+        long,
+        help = "Do not warn when the ciphertext entropy suggests compressed/encrypted data"
+    )]
+    pub no_entropy_warning: bool,
+}
+
+impl Default for Parameters {
+    /// The parameters the tool runs with when no arguments are given, so
+    /// parameters built in code get the same defaults (and key cap) as the CLI
+    fn default() -> Self {
+        Self {
+            brute_chars: false,
+            brute_printable: false,
+            filenames: vec![String::from(STDIN_FILENAME)],
+            filter_output: false,
+            out_ext: None,
+            show_best: None,
+            charset_report: false,
+            input_is_hex: false,
+            hexdump: false,
+            escaped: false,
+            #[cfg(feature = "gzip")]
+            gzip_input: false,
+            key_lengths: Vec::new(),
+            keylen_pct: None,
+            scan_lengths: None,
+            prefer_length: None,
+            prefer_boost: None,
+            known_key_length: None,
+            use_divisor: false,
+            all_divisors: false,
+            max_key_length: None,
+            multiple_of: None,
+            most_frequent_char: None,
+            auto_char: false,
+            text_charset: PREDEFINED_CHARSETS[DEFAULT_CHARSET].as_bytes().to_vec(),
+            utf8_score: false,
+            charset_ignore_case: false,
+            wide: None,
+            known_plain: None,
+            seed_key: None,
+            threshold: None,
+            histogram: None,
+            only_key_length: false,
+            quiet: false,
+            keep_output: false,
+            keep_going: false,
+            append_csv: false,
+            csv_delimiter: None,
+            first_n_bytes_printable: None,
+            stdin_name: None,
+            benchmark: false,
+            echo_params: false,
+            timeout: None,
+            deadline: None,
+            #[cfg(feature = "tar")]
+            tar: None,
+            report: false,
+            fitness_out: None,
+            completions: None,
+            list_charsets: false,
+            detailed_exit_codes: false,
+            line_ending: LineEnding::default(),
+            column_entropy: false,
+            dump_columns: false,
+            printable_key: false,
+            guess_only_printable_keys: false,
+            drop_null_key: false,
+            sort_by_validity: false,
+            max_candidate_keys: DEFAULT_MAX_CANDIDATE_KEYS,
+            max_total_candidate_keys: None,
+            unlimited_candidate_keys: false,
+            show_ambiguity: false,
+            key_confidence: false,
+            compare: None,
+            retain_below_threshold: false,
+            skip_head: 0,
+            skip_tail: 0,
+            input_offset: None,
+            input_length: None,
+            sample_bytes: None,
+            score: Score::default(),
+            autocorr: false,
+            key_format: KeyFormat::default(),
+            key_both: false,
+            key_period: false,
+            print0: false,
+            key_lang: KeyLang::default(),
+            entropy: false,
+            no_entropy_warning: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_seed_key() {
+        assert_eq!(
+            parse_seed_key("s?\\x41\\??").unwrap(),
+            vec![Some(b's'), None, Some(0x41), Some(b'?'), None]
+        );
+        parse_seed_key("").unwrap_err();
+        parse_seed_key("\\xZZ").unwrap_err();
+        parse_seed_key("ab\\").unwrap_err();
+    }
+
+    #[test]
+    fn test_double_dash_filename() {
+        let param = Parameters::try_parse_from(["xortool", "-c", "20", "--", "-x"]).unwrap();
+        assert_eq!(param.filenames, vec![String::from("-x")]);
+        assert!(!param.input_is_hex);
+    }
+
+    #[test]
+    fn test_completions() {
+        let param = Parameters::try_parse_from(["xortool", "--completions", "zsh"]).unwrap();
+        assert_eq!(param.completions, Some(Shell::Zsh));
+    }
+
+    #[test]
+    fn test_single_key_length() {
+        let param = Parameters::try_parse_from(["xortool", "-l", "10"]).unwrap();
+        assert_eq!(param.key_lengths, vec![10]);
+    }
+
+    #[test]
+    fn test_multiple_key_lengths() {
+        let param = Parameters::try_parse_from(["xortool", "-l", "8,16"]).unwrap();
+        assert_eq!(param.key_lengths, vec![8, 16]);
+    }
+
+    #[test]
+    fn test_zero_key_length() {
+        Parameters::try_parse_from(["xortool", "-l", "8,0"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(parse_timeout("1.5"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_timeout("0"), Ok(Duration::ZERO));
+        for bad in ["", "soon", "-1", "NaN"] {
+            assert!(matches!(
+                parse_timeout(bad),
+                Err(XorError::ArgParser { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_default_matches_cli() {
+        let default = Parameters::default();
+        let parsed = Parameters::try_parse_from(["xortool"]).unwrap();
+        assert_eq!(default.filenames, parsed.filenames);
+        assert_eq!(default.text_charset, parsed.text_charset);
+        assert_eq!(default.max_candidate_keys, parsed.max_candidate_keys);
+        assert_eq!(default.score, parsed.score);
+        assert_eq!(default.key_format, parsed.key_format);
+        assert_eq!(default.line_ending, parsed.line_ending);
+    }
+
+    #[test]
+    fn test_max_candidate_keys_range() {
+        let param = Parameters::try_parse_from(["xortool", "--max-candidate-keys", "1"]).unwrap();
+        assert_eq!(param.max_candidate_keys, 1);
+        Parameters::try_parse_from(["xortool", "--max-candidate-keys", "0"]).unwrap_err();
+        Parameters::try_parse_from(["xortool", "--max-candidate-keys", "-3"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_prefer_boost() {
+        assert_eq!(parse_prefer_boost("1.5"), Ok(1.5));
+        assert_eq!(parse_prefer_boost("1"), Ok(1.0));
+        for bad in ["", "big", "0.5", "-2", "inf", "NaN"] {
+            assert!(matches!(
+                parse_prefer_boost(bad),
+                Err(XorError::ArgParser { .. })
+            ));
+        }
+        Parameters::try_parse_from(["xortool", "--prefer-boost", "2"]).unwrap_err();
+        Parameters::try_parse_from(["xortool", "--prefer-length", "4", "-l", "4"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_csv_delimiter() {
+        assert_eq!(parse_csv_delimiter(","), Ok(b','));
+        assert_eq!(parse_csv_delimiter("\t"), Ok(b'\t'));
+        for bad in ["", ",,", "\"", "\n", "\u{e9}"] {
+            assert!(matches!(
+                parse_csv_delimiter(bad),
+                Err(XorError::ArgParser { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_most_frequent_char() {
+        assert_eq!(parse_most_frequent(" "), Ok(32));
+    }
+
+    #[test]
+    fn test_parse_most_frequent_hex() {
+        assert_eq!(parse_most_frequent("0x20"), Ok(32));
+        assert_eq!(parse_most_frequent("\\x20"), Ok(32));
+        assert_eq!(parse_most_frequent("20"), Ok(32));
+    }
+
+    #[test]
+    fn test_parse_most_frequent_decimal() {
+        assert_eq!(parse_most_frequent("d:32"), Ok(32));
+        assert_eq!(parse_most_frequent("d:255"), Ok(255));
+    }
+
+    #[test]
+    fn test_parse_most_frequent_decimal_too_large() {
+        assert!(matches!(
+            parse_most_frequent("d:256"),
+            Err(XorError::ArgParser { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_most_frequent_bad_hex() {
+        assert!(matches!(
+            parse_most_frequent("zz"),
+            Err(XorError::ArgParser { .. })
+        ));
+    }
 }
@@ -7,5 +7,6 @@
 use xortool_rs::libcolors;
 
 fn main() {
+    libcolors::init_colors();
     libcolors::_main();
 }
@@ -4,8 +4,12 @@
 * file, You can obtain one at https: //mozilla.org/MPL/2.0/.
 */
 //! Binary for the xortool-xor executable
+use std::process::ExitCode;
+
 use xortool_rs::tool_xor;
 
-fn main() {
-    tool_xor::main(None);
+fn main() -> ExitCode {
+    #[cfg(feature = "logging")]
+    env_logger::init();
+    tool_xor::main(None)
 }
@@ -4,8 +4,16 @@
 * file, You can obtain one at https: //mozilla.org/MPL/2.0/.
 */
 //! Binary for the xortool executable
+use std::process::ExitCode;
+
+#[cfg(feature = "color")]
+use xortool_rs::libcolors;
 use xortool_rs::tool_main;
 
-fn main() {
-    tool_main::main(None);
+fn main() -> ExitCode {
+    #[cfg(feature = "logging")]
+    env_logger::init();
+    #[cfg(feature = "color")]
+    libcolors::init_colors();
+    tool_main::main(None)
 }
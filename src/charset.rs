@@ -8,7 +8,7 @@ use std::collections::HashMap;
 use std::fmt::Write as _;
 use std::sync::LazyLock;
 
-use crate::error::XorError;
+use crate::{error::XorError, routine::line_end};
 // FIXME: Generally, there's a lot here that could be cleaned up
 
 /// Mapping of the short forms to sets of characters
@@ -23,6 +23,9 @@ static CHARSETS: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(|
     m
 });
 
+/// Every ASCII character (0x00 to 0x7F)
+static ASCII_CHARS: LazyLock<String> = LazyLock::new(|| (0..=0x7F).map(char::from).collect());
+
 /// Mapping of some pre-defined character sets.
 pub static PREDEFINED_CHARSETS: LazyLock<HashMap<&'static str, &'static str>> =
     LazyLock::new(|| {
@@ -32,23 +35,66 @@ pub static PREDEFINED_CHARSETS: LazyLock<HashMap<&'static str, &'static str>> =
             "base64",
             "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789/+=",
         );
+        m.insert("base16", "0123456789abcdefABCDEF");
         m.insert("printable", CHARSETS.get("*").unwrap());
+        m.insert("ascii", ASCII_CHARS.as_str());
+        m.insert(
+            "alnum",
+            "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789",
+        );
         m
     });
 
-/// Get a character from the short form combination
+/// Get a character set for display
+///
+/// # Arguments
+///   * `charset`: The charset, as accepted by `get_charset_bytes`
+///
+/// # Returns
+///   The characters of the set, with non-printable bytes escaped
+///
+/// # Errors
+///   Returns any error from `get_charset_bytes`
+pub fn get_charset(charset: &str) -> Result<String, XorError> {
+    Ok(get_charset_bytes(charset)?.escape_ascii().to_string())
+}
+
+/// Get the bytes of a character set from its name or short form combination
+///
+/// A `+` followed by characters adds them to the set, and a `-` followed
+/// by characters removes them, e.g. `printable-\t\n\r ` is printable
+/// without whitespace and `1+\xff` is the digits and the byte 0xFF. The
+/// added and removed characters can use the escapes `\t`, `\n`, `\r`, `\\`
+/// and `\xAB`, so the set can hold bytes that aren't valid UTF-8.
 ///
 /// # Arguments
 ///   * `charset`: Either the name of a predefined charset to use, or
-///     A set of characters for the to combine
+///     A set of characters for the to combine, optionally followed by `+`
+///     and the characters to add, and `-` and the characters to remove
 ///
 /// # Returns
 ///   Created character set
 ///
 /// # Errors
-///   Returns `XorError::CharsetError` if an invalid letter is used for
-///   building a charset.
-pub fn get_charset(charset: &str) -> Result<Vec<u8>, XorError> {
+///   Returns `XorError::Charset` if an invalid letter is used for
+///   building a charset, or an invalid escape in the added or removed
+///   characters.
+pub fn get_charset_bytes(charset: &str) -> Result<Vec<u8>, XorError> {
+    if let Some((base, excluded)) = charset.split_once('-') {
+        let excluded = parse_escaped_chars(excluded)?;
+        let mut chars = get_charset_bytes(base)?;
+        chars.retain(|c| !excluded.contains(c));
+        return Ok(chars);
+    }
+    if let Some((base, added)) = charset.split_once('+') {
+        let mut chars = get_charset_bytes(base)?;
+        for byte in parse_escaped_chars(added)? {
+            if !chars.contains(&byte) {
+                chars.push(byte);
+            }
+        }
+        return Ok(chars);
+    }
     let charset = if charset.is_empty() {
         "printable"
     } else {
@@ -61,15 +107,81 @@ pub fn get_charset(charset: &str) -> Result<Vec<u8>, XorError> {
             .to_vec());
     }
 
-    let mut chars = String::new();
+    let mut chars = Vec::new();
     for c in charset.chars() {
-        if CHARSETS.contains_key(c.to_string().as_str()) {
-            write!(chars, "{}", CHARSETS.get(c.to_string().as_str()).unwrap()).unwrap();
+        if let Some(&short_form) = CHARSETS.get(c.to_string().as_str()) {
+            chars.extend_from_slice(short_form.as_bytes());
         } else {
             return Err(XorError::Charset { charset: c });
         }
     }
-    Ok(chars.as_bytes().to_vec())
+    Ok(chars)
+}
+
+/// Describe every charset that `get_charset_bytes` accepts
+///
+/// # Returns
+///   One line per predefined charset and per short form letter, giving the
+///   characters in it with non-printable ones escaped.
+pub fn charset_listing() -> String {
+    let line_end = line_end();
+    let mut listing = format!("Predefined charsets:{line_end}");
+    let mut names: Vec<&str> = PREDEFINED_CHARSETS.keys().copied().collect();
+    names.sort_unstable();
+    for name in names {
+        let chars = get_charset(name).unwrap();
+        write!(listing, "  {name:<9}: {chars}{line_end}").unwrap();
+    }
+    write!(
+        listing,
+        "Short forms (combine letters, e.g. -t aA1):{line_end}"
+    )
+    .unwrap();
+    let mut letters: Vec<&str> = CHARSETS.keys().copied().collect();
+    letters.sort_unstable();
+    for letter in letters {
+        let chars = get_charset(letter).unwrap();
+        write!(listing, "  {letter}: {chars}{line_end}").unwrap();
+    }
+    listing
+}
+
+/// Parse the characters to add to or remove from a charset
+///
+/// # Arguments
+///   * `escaped`: The characters, with `\t`, `\n`, `\r`, `\\` and `\xAB`
+///     escapes
+///
+/// # Returns
+///   The bytes to add or remove
+///
+/// # Errors
+///   Returns `XorError::Charset` with the backslash if an escape is invalid
+fn parse_escaped_chars(escaped: &str) -> Result<Vec<u8>, XorError> {
+    let bad_escape = XorError::Charset { charset: '\\' };
+    let mut bytes = Vec::new();
+    let mut chars = escaped.bytes();
+    while let Some(byte) = chars.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        bytes.push(match chars.next() {
+            Some(b't') => b'\t',
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b'\\') => b'\\',
+            Some(b'x') => {
+                let hex: Vec<u8> = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&String::from_utf8_lossy(&hex), 16)
+                    .ok()
+                    .filter(|_| hex.len() == 2)
+                    .ok_or_else(|| bad_escape.clone())?
+            }
+            _ => return Err(bad_escape),
+        });
+    }
+    Ok(bytes)
 }
 
 #[cfg(test)]
@@ -79,7 +191,7 @@ mod tests {
     #[test]
     fn test_empty_str() {
         assert_eq!(
-            get_charset(""),
+            get_charset_bytes(""),
             Ok(PREDEFINED_CHARSETS["printable"]
                 .to_string()
                 .as_bytes()
@@ -91,16 +203,49 @@ mod tests {
     fn test_predefined_charsets() {
         for c in PREDEFINED_CHARSETS.keys() {
             assert_eq!(
-                get_charset(c),
+                get_charset_bytes(c),
                 Ok(PREDEFINED_CHARSETS[c].to_string().as_bytes().to_vec())
             )
         }
     }
 
+    #[test]
+    fn test_charset_listing() {
+        let listing = charset_listing();
+        for name in PREDEFINED_CHARSETS.keys() {
+            assert!(listing.contains(&format!("  {name:<9}: ")), "{name}");
+        }
+        for letter in CHARSETS.keys() {
+            assert!(listing.contains(&format!("  {letter}: ")), "{letter}");
+        }
+        assert!(listing.contains("  base16   : 0123456789abcdefABCDEF"));
+        assert!(listing.contains("\\x00\\x01"));
+    }
+
+    #[test]
+    fn test_base16_charset() {
+        assert_eq!(
+            get_charset_bytes("base16"),
+            Ok("0123456789abcdefABCDEF".as_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn test_ascii_charset() {
+        assert_eq!(get_charset_bytes("ascii"), Ok((0..=0x7F).collect()));
+    }
+
+    #[test]
+    fn test_alnum_charset() {
+        let charset = get_charset_bytes("alnum").unwrap();
+        assert_eq!(charset.len(), 62);
+        assert!(charset.iter().all(u8::is_ascii_alphanumeric));
+    }
+
     #[test]
     fn test_building_charset() {
         assert_eq!(
-            get_charset("aA"),
+            get_charset_bytes("aA"),
             Ok(
                 String::from("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ")
                     .as_bytes()
@@ -109,8 +254,73 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_subtract_from_printable() {
+        let charset = get_charset_bytes("printable-\\t\\n\\r \\x0b\\x0c").unwrap();
+        assert_eq!(charset.len(), PREDEFINED_CHARSETS["printable"].len() - 6);
+        assert!(!charset.iter().any(u8::is_ascii_whitespace));
+        assert!(!charset.contains(&0x0b));
+        assert!(charset.contains(&b'a'));
+    }
+
+    #[test]
+    fn test_subtract_from_custom() {
+        assert_eq!(get_charset_bytes("1-13579"), Ok(b"02468".to_vec()));
+        // An empty set before the `-` means printable
+        assert_eq!(get_charset_bytes("-"), get_charset_bytes("printable"));
+    }
+
+    #[test]
+    fn test_subtract_errors() {
+        assert_eq!(
+            get_charset_bytes("aZ-b"),
+            Err(XorError::Charset { charset: 'Z' })
+        );
+        assert_eq!(
+            get_charset_bytes("a-\\q"),
+            Err(XorError::Charset { charset: '\\' })
+        );
+        assert_eq!(
+            get_charset_bytes("a-\\x4"),
+            Err(XorError::Charset { charset: '\\' })
+        );
+    }
+
+    #[test]
+    fn test_add_to_charset() {
+        // Not valid UTF-8, so it can only be built as bytes
+        let charset = get_charset_bytes("1+\\xff\\xfe").unwrap();
+        assert_eq!(charset, b"0123456789\xff\xfe");
+        String::from_utf8(charset).unwrap_err();
+        assert_eq!(get_charset("1+\\xff"), Ok(String::from("0123456789\\xff")));
+        // Bytes already in the set are not added twice
+        assert_eq!(get_charset_bytes("1+0a"), Ok(b"0123456789a".to_vec()));
+        assert_eq!(
+            get_charset_bytes("1+\\xff-0"),
+            Ok(b"123456789\xff".to_vec())
+        );
+        assert_eq!(
+            get_charset_bytes("1+\\x"),
+            Err(XorError::Charset { charset: '\\' })
+        );
+    }
+
+    #[test]
+    fn test_get_charset_display() {
+        assert_eq!(get_charset("1"), Ok(String::from("0123456789")));
+        assert!(
+            get_charset("printable")
+                .unwrap()
+                .ends_with(" \\t\\n\\r\\x0b\\x0c")
+        );
+        assert_eq!(get_charset("aZ"), Err(XorError::Charset { charset: 'Z' }));
+    }
+
     #[test]
     fn test_invalid_charset() {
-        assert_eq!(get_charset("aZ"), Err(XorError::Charset { charset: 'Z' }))
+        assert_eq!(
+            get_charset_bytes("aZ"),
+            Err(XorError::Charset { charset: 'Z' })
+        )
     }
 }
@@ -0,0 +1,205 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! The repeating-key xor transform
+use std::{
+    iter::{Copied, Cycle},
+    slice::Iter,
+};
+
+/// Xor several pieces of data together
+///
+/// The output is as long as the longest input. With `cycle` set, each
+/// shorter input is repeated until it covers the whole output. Without it,
+/// each shorter input only changes the start of the output, and the rest
+/// is left as it is in the longest input.
+///
+/// # Arguments
+///   * `args`: The data to xor together
+///   * `cycle`: Whether to repeat the shorter inputs over the longest one
+///
+/// # Returns
+///   The xor of all of the data, or an empty vector if there is no data
+///
+/// # Examples
+///
+/// ```
+/// use xortool_rs::cipher::xor;
+///
+/// let inputs = vec![b"abcd".to_vec(), vec![0x20]];
+/// assert_eq!(xor(inputs.clone(), true), b"ABCD");
+/// assert_eq!(xor(inputs, false), b"Abcd");
+///
+/// // Xoring the same data twice cancels it out
+/// let data = b"Hello".to_vec();
+/// let key = b"key".to_vec();
+/// assert_eq!(xor(vec![xor(vec![data.clone(), key.clone()], true), key], true), data);
+/// ```
+pub fn xor(mut args: Vec<Vec<u8>>, cycle: bool) -> Vec<u8> {
+    args.sort_by_key(Vec::len);
+    let Some(mut res) = args.pop() else {
+        return Vec::new();
+    };
+    let maxlen = res.len();
+    for s in args {
+        let range_end = if cycle { maxlen } else { s.len() };
+        for (byte, other) in res.iter_mut().take(range_end).zip(s.iter().cycle()) {
+            *byte ^= other;
+        }
+    }
+    res
+}
+
+/// A key that is repeated over the data it is xored with
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct XorKey(Vec<u8>);
+
+impl XorKey {
+    /// Get the bytes of the key
+    ///
+    /// # Returns
+    ///   Slice of the key bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Get the length of the key
+    ///
+    /// # Returns
+    ///   Number of bytes in the key
+    pub const fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if the key has no bytes
+    ///
+    /// # Returns
+    ///   True if the key is empty
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate over the key bytes, repeating forever
+    ///
+    /// # Returns
+    ///   Endless iterator of the key bytes. Yields nothing if the key is empty.
+    pub fn cycle(&self) -> Cycle<Copied<Iter<'_, u8>>> {
+        self.0.iter().copied().cycle()
+    }
+
+    /// Xor data in place with the repeating key
+    ///
+    /// # Arguments
+    ///   * `data`: The data to xor. Left unchanged if the key is empty.
+    pub fn cycle_xor_in_place(&self, data: &mut [u8]) {
+        self.cycle_xor_in_place_at(data, 0);
+    }
+
+    /// Xor a chunk of a longer stream in place with the repeating key
+    ///
+    /// # Arguments
+    ///   * `data`: The chunk to xor. Left unchanged if the key is empty.
+    ///   * `position`: Position of the start of the chunk in the stream
+    pub fn cycle_xor_in_place_at(&self, data: &mut [u8], position: usize) {
+        let skip = position.checked_rem(self.len()).unwrap_or_default();
+        for (byte, key_byte) in data.iter_mut().zip(self.cycle().skip(skip)) {
+            *byte ^= key_byte;
+        }
+    }
+
+    /// Xor data with the repeating key
+    ///
+    /// # Arguments
+    ///   * `data`: The data to xor
+    ///
+    /// # Returns
+    ///   The xored data. A copy of `data` if the key is empty.
+    pub fn cycle_xor(&self, data: &[u8]) -> Vec<u8> {
+        let mut result = data.to_vec();
+        self.cycle_xor_in_place(&mut result);
+        result
+    }
+}
+
+impl From<Vec<u8>> for XorKey {
+    fn from(value: Vec<u8>) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&[u8]> for XorKey {
+    fn from(value: &[u8]) -> Self {
+        Self(value.to_vec())
+    }
+}
+
+impl AsRef<[u8]> for XorKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xor_three_inputs() {
+        let result = xor(vec![b"ab".to_vec(), b"Hello".to_vec(), vec![1]], true);
+        assert_eq!(
+            result,
+            vec![
+                b'H' ^ b'a' ^ 1,
+                b'e' ^ b'b' ^ 1,
+                b'l' ^ b'a' ^ 1,
+                b'l' ^ b'b' ^ 1,
+                b'o' ^ b'a' ^ 1
+            ]
+        );
+    }
+
+    #[test]
+    fn test_xor_empty() {
+        assert!(xor(Vec::new(), true).is_empty());
+        assert_eq!(xor(vec![b"abc".to_vec(), Vec::new()], true), b"abc");
+    }
+
+    #[test]
+    fn test_cycle() {
+        let key = XorKey::from(b"abc".as_slice());
+        assert_eq!(key.cycle().take(7).collect::<Vec<u8>>(), b"abcabca");
+    }
+
+    #[test]
+    fn test_cycle_xor() {
+        let key = XorKey::from(vec![0x01, 0x02]);
+        assert_eq!(key.cycle_xor(b"\x00\x00\x00"), vec![0x01, 0x02, 0x01]);
+        assert_eq!(key.cycle_xor(&key.cycle_xor(b"Hello")), b"Hello");
+    }
+
+    #[test]
+    fn test_cycle_xor_long_key() {
+        let key = XorKey::from(b"secret".as_slice());
+        assert_eq!(key.cycle_xor(b"Hi"), vec![b'H' ^ b's', b'i' ^ b'e']);
+    }
+
+    #[test]
+    fn test_cycle_xor_in_chunks() {
+        let key = XorKey::from(b"abc".as_slice());
+        let data = b"Hello World, in chunks";
+        let mut chunked = data.to_vec();
+        let (first, second) = chunked.split_at_mut(5);
+        key.cycle_xor_in_place_at(first, 0);
+        key.cycle_xor_in_place_at(second, 5);
+        assert_eq!(chunked, key.cycle_xor(data));
+    }
+
+    #[test]
+    fn test_cycle_xor_empty_key() {
+        let key = XorKey::default();
+        assert!(key.is_empty());
+        assert_eq!(key.cycle_xor(b"Hello"), b"Hello");
+    }
+}
@@ -6,33 +6,129 @@
 //! Custom bash color definitions for the tool
 use std::sync::LazyLock;
 
-use crate::libcolors::color;
-/// Reset the console color back to normal
-pub static C_RESET: LazyLock<String> = LazyLock::new(|| color("", "", ""));
+#[cfg(feature = "color")]
+use crate::libcolors::{self, color};
 
-/// Console color used to display fatal error messages
-pub static C_FATAL: LazyLock<String> = LazyLock::new(|| color("red", "", ""));
+/// Define a console color, which is empty when built without the `color` feature
+///
+/// Both kinds are a `LazyLock<String>`, so the colors are used the same way
+/// either way.
+macro_rules! console_color {
+    ($(#[$doc:meta])* $name:ident = ($fg:literal, $bg:literal, $attrs:literal)) => {
+        $(#[$doc])*
+        #[cfg(feature = "color")]
+        pub static $name: LazyLock<String> = LazyLock::new(|| color($fg, $bg, $attrs));
 
-/// Console color to display warnings
-pub static C_WARN: LazyLock<String> = LazyLock::new(|| color("yellow", "", ""));
+        $(#[$doc])*
+        #[cfg(not(feature = "color"))]
+        pub static $name: LazyLock<String> = LazyLock::new(String::new);
+    };
+}
 
-/// Console color to display key lengths in
-pub static C_KEYLEN: LazyLock<String> = LazyLock::new(|| color("green", "", ""));
+console_color! {
+    /// Reset the console color back to normal
+    C_RESET = ("", "", "")
+}
 
-/// Console color to display probability in
-pub static C_PROB: LazyLock<String> = LazyLock::new(|| color("white", "", ""));
+console_color! {
+    /// Console color used to display fatal error messages
+    C_FATAL = ("red", "", "")
+}
 
-/// Console color to display best key length in
-pub static C_BEST_KEYLEN: LazyLock<String> = LazyLock::new(|| color("green", "", "bold"));
+console_color! {
+    /// Console color to display warnings
+    C_WARN = ("yellow", "", "")
+}
 
-/// Console color to display best probability in
-pub static C_BEST_PROB: LazyLock<String> = LazyLock::new(|| color("white", "", "bold"));
+console_color! {
+    /// Console color to display key lengths in
+    C_KEYLEN = ("green", "", "")
+}
 
-/// Console color to display key-length divisor in
-pub static C_DIV: LazyLock<String> = LazyLock::new(|| color("", "", "bold"));
+console_color! {
+    /// Console color to display probability in
+    C_PROB = ("white", "", "")
+}
 
-/// Console color to display keys in
-pub static C_KEY: LazyLock<String> = LazyLock::new(|| color("red", "", "bold"));
+console_color! {
+    /// Console color to display best key length in
+    C_BEST_KEYLEN = ("green", "", "bold")
+}
 
-/// Console color to display counts in.
-pub static C_COUNT: LazyLock<String> = LazyLock::new(|| color("yellow", "", "bold"));
+console_color! {
+    /// Console color to display best probability in
+    C_BEST_PROB = ("white", "", "bold")
+}
+
+console_color! {
+    /// Console color to display key-length divisor in
+    C_DIV = ("", "", "bold")
+}
+
+console_color! {
+    /// Console color to display keys in
+    C_KEY = ("red", "", "bold")
+}
+
+console_color! {
+    /// Console color to display counts in.
+    C_COUNT = ("yellow", "", "bold")
+}
+
+/// Make text a hyperlink in terminals that support it
+///
+/// # Arguments
+///  * `url`: The URL to link to
+///  * `text`: The text to display
+///
+/// # Returns
+///   The text, wrapped in a hyperlink if hyperlinks are enabled.
+#[cfg(feature = "color")]
+pub fn hyperlink(url: &str, text: &str) -> String {
+    libcolors::hyperlink(url, text)
+}
+
+/// Hyperlinks are escape sequences too, so they are left out without the
+/// `color` feature
+///
+/// # Arguments
+///  * `_url`: The URL that would be linked to
+///  * `text`: The text to display
+///
+/// # Returns
+///   The text, unchanged
+#[cfg(not(feature = "color"))]
+pub fn hyperlink(_url: &str, text: &str) -> String {
+    text.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(feature = "color"))]
+    fn test_no_color() {
+        for color in [
+            &C_RESET,
+            &C_FATAL,
+            &C_WARN,
+            &C_KEYLEN,
+            &C_PROB,
+            &C_BEST_KEYLEN,
+            &C_BEST_PROB,
+            &C_DIV,
+            &C_KEY,
+            &C_COUNT,
+        ] {
+            assert!(color.is_empty());
+        }
+        assert_eq!(hyperlink("file:///tmp/0.out", "0.out"), "0.out");
+    }
+
+    #[test]
+    #[cfg(feature = "color")]
+    fn test_reset_matches_color() {
+        assert_eq!(*C_RESET, color("", "", ""));
+    }
+}
@@ -4,9 +4,26 @@
 * file, You can obtain one at https: //mozilla.org/MPL/2.0/.
 */
 //! Custom error type for the tool
-use std::{env, error::Error, fmt, io};
+use std::{error::Error, fmt, io};
+
+use crate::routine::line_end;
+
+/// Exit code for a bad command line argument or charset
+pub const EXIT_ARGUMENT: u8 = 2;
+/// Exit code for a failure reading or writing files
+pub const EXIT_IO: u8 = 3;
+/// Exit code for when the analysis could not find an answer
+pub const EXIT_ANALYSIS: u8 = 4;
+/// Exit code for input data that could not be decoded
+pub const EXIT_DECODE: u8 = 5;
+/// Exit code for when the analysis ran out of time
+pub const EXIT_TIMEOUT: u8 = 6;
 
 /// Enumeration of errors the tool may experience.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "XorError is used unqualified throughout, so the name is kept for clarity."
+)]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum XorError {
     /// An error occurred during analysis of the data
@@ -40,6 +57,27 @@ pub enum XorError {
         /// Message with further details about the errorr
         msg: String,
     },
+    /// The analysis took longer than the time it was given
+    Timeout {
+        /// Message saying how far the analysis got
+        msg: String,
+    },
+}
+
+impl XorError {
+    /// Get the exit code used for this error with `--detailed-exit-codes`
+    ///
+    /// # Returns
+    ///   One of the `EXIT_*` constants, depending on the kind of error
+    pub const fn exit_code(&self) -> u8 {
+        match self {
+            Self::ArgParser { .. } | Self::Charset { .. } => EXIT_ARGUMENT,
+            Self::IO { .. } | Self::Mkdir { .. } => EXIT_IO,
+            Self::Analysis { .. } => EXIT_ANALYSIS,
+            Self::UnicodeDecode { .. } => EXIT_DECODE,
+            Self::Timeout { .. } => EXIT_TIMEOUT,
+        }
+    }
 }
 
 impl fmt::Display for XorError {
@@ -54,12 +92,9 @@ impl fmt::Display for XorError {
             Self::Mkdir { msg } => ("Can't create directory", msg.clone()),
             Self::UnicodeDecode { msg } => ("Input is not hex", msg.clone()),
             Self::ArgParser { msg } => ("Bad argument", msg.clone()),
+            Self::Timeout { msg } => ("Timed out", msg.clone()),
         };
-        if env::consts::OS == "windows" {
-            write!(f, "[ERROR] {type_str}:\r\n\t{details}")
-        } else {
-            write!(f, "[ERROR] {type_str}:\n\t{details}")
-        }
+        write!(f, "[ERROR] {type_str}:{}\t{details}", line_end())
     }
 }
 impl Error for XorError {}
@@ -88,16 +123,55 @@ impl From<clap::error::Error> for XorError {
 
 impl From<io::Error> for XorError {
     fn from(value: io::Error) -> Self {
+        #[expect(
+            clippy::wildcard_enum_match_arm,
+            reason = "Only the common kinds get a custom description, the rest use the default one."
+        )]
+        let description = match value.kind() {
+            io::ErrorKind::NotFound => String::from("File not found"),
+            io::ErrorKind::PermissionDenied => String::from("Permission denied"),
+            io::ErrorKind::IsADirectory => String::from("Path is a directory"),
+            io::ErrorKind::UnexpectedEof => String::from("Unexpected end of file"),
+            kind => kind.to_string(),
+        };
         XorError::IO {
-            msg: value.to_string(),
+            msg: format!("{description}: {value}"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::env;
+
     use super::*;
 
+    #[test]
+    fn test_variant_names() {
+        // Exhaustive match, so renaming or adding a variant must update this test.
+        let errors = [
+            XorError::Analysis { msg: String::new() },
+            XorError::Charset { charset: 'Q' },
+            XorError::IO { msg: String::new() },
+            XorError::Mkdir { msg: String::new() },
+            XorError::UnicodeDecode { msg: String::new() },
+            XorError::ArgParser { msg: String::new() },
+            XorError::Timeout { msg: String::new() },
+        ];
+        for err in errors {
+            let type_str = match err {
+                XorError::Analysis { .. } => "Analysis error",
+                XorError::Charset { .. } => "Bad charset",
+                XorError::IO { .. } => "Can't load file",
+                XorError::Mkdir { .. } => "Can't create directory",
+                XorError::UnicodeDecode { .. } => "Input is not hex",
+                XorError::ArgParser { .. } => "Bad argument",
+                XorError::Timeout { .. } => "Timed out",
+            };
+            assert!(err.to_string().starts_with(&format!("[ERROR] {type_str}:")));
+        }
+    }
+
     #[test]
     fn test_fmt_analysis_error() {
         let err = XorError::Analysis {
@@ -154,6 +228,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_from_io_error_not_found() {
+        let err = XorError::from(io::Error::new(io::ErrorKind::NotFound, "missing.bin"));
+        assert_eq!(
+            err,
+            XorError::IO {
+                msg: String::from("File not found: missing.bin")
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_io_error_permission_denied() {
+        let err = XorError::from(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "secret.bin",
+        ));
+        assert_eq!(
+            err,
+            XorError::IO {
+                msg: String::from("Permission denied: secret.bin")
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_io_error_other_kind() {
+        let err = XorError::from(io::Error::new(io::ErrorKind::InvalidData, "bad"));
+        assert_eq!(
+            err,
+            XorError::IO {
+                msg: format!("{}: bad", io::ErrorKind::InvalidData)
+            }
+        );
+    }
+
     #[test]
     fn test_mkdir_error() {
         let err = XorError::Mkdir {
@@ -179,4 +289,24 @@ mod tests {
             assert_eq!(err.to_string(), "[ERROR] Input is not hex:\n\tABCD");
         }
     }
+
+    #[test]
+    fn test_exit_codes() {
+        let msg = String::from("ABCD");
+        assert_eq!(
+            XorError::ArgParser { msg: msg.clone() }.exit_code(),
+            EXIT_ARGUMENT
+        );
+        assert_eq!(
+            XorError::Charset { charset: 'Q' }.exit_code(),
+            EXIT_ARGUMENT
+        );
+        assert_eq!(XorError::IO { msg: msg.clone() }.exit_code(), EXIT_IO);
+        assert_eq!(XorError::Mkdir { msg: msg.clone() }.exit_code(), EXIT_IO);
+        assert_eq!(
+            XorError::Analysis { msg: msg.clone() }.exit_code(),
+            EXIT_ANALYSIS
+        );
+        assert_eq!(XorError::UnicodeDecode { msg }.exit_code(), EXIT_DECODE);
+    }
 }
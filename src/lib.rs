@@ -4,10 +4,13 @@
 * file, You can obtain one at https: //mozilla.org/MPL/2.0/.
 */
 //! Library holding the code for xortool
+pub mod api;
 mod args;
 mod charset;
+pub mod cipher;
 mod colors;
-mod error;
+pub mod error;
+#[cfg(feature = "color")]
 pub mod libcolors;
 mod routine;
 pub mod tool_main;
@@ -5,7 +5,20 @@
 */
 //! Support for bash colors
 // FIXME: Probably could replace this whole thing with some sort of crate.
-use std::{collections::HashMap, env, fmt::Write as _, string, sync::LazyLock};
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Write as _,
+    io::{self, IsTerminal as _},
+    string,
+    sync::{
+        LazyLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// Whether the terminal was found to be unable to process ANSI escapes
+static ANSI_UNSUPPORTED: AtomicBool = AtomicBool::new(false);
 
 /// Table of different attributes supported by bash, and their integer codes
 static BASH_ATTRIBUTES: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(|| {
@@ -48,6 +61,20 @@ static BASH_BGCOLORS: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::
     m
 });
 
+/// Prepare the terminal for displaying colors
+///
+/// On Windows, this switches the console into virtual terminal mode so
+/// that ANSI escape sequences are rendered instead of printed literally.
+/// If that fails, colors are disabled. On other platforms this does nothing.
+///
+/// This should be called once, from the entry point of a binary.
+pub fn init_colors() {
+    #[cfg(windows)]
+    if enable_ansi_support::enable_ansi_support().is_err() {
+        ANSI_UNSUPPORTED.store(true, Ordering::Relaxed);
+    }
+}
+
 /// Main function for the colortest program.
 pub fn _main() {
     let header = color("white", "black", "dark");
@@ -87,6 +114,9 @@ pub fn _main() {
 
 /// Create a single POSIX color/attribute setting string
 ///
+/// Colors can either be one of the named colors, a 256-color palette
+/// index (e.g. `256:208`), or a 24-bit color (e.g. `rgb:255,128,0`).
+///
 /// # Arguments
 ///  * `color`: The foreground color to set
 ///  * `bgcolor`: The background color to set
@@ -99,7 +129,7 @@ pub fn _main() {
 ///   This function will panic if an unknown color, background color, or
 ///   attribute is supplied
 pub fn color(color: &str, bgcolor: &str, attrs: &str) -> String {
-    if !is_bash() {
+    if !is_bash() || ANSI_UNSUPPORTED.load(Ordering::Relaxed) {
         return String::new();
     }
 
@@ -117,23 +147,97 @@ pub fn color(color: &str, bgcolor: &str, attrs: &str) -> String {
     }
 
     if !color.is_empty() {
-        assert!(BASH_COLORS.contains_key(&color), "Unknown color: {color}");
-
-        write!(ret, ";{}", BASH_COLORS.get(&color).unwrap()).unwrap();
+        if let Some(code) = extended_color(color, "38") {
+            write!(ret, ";{code}").unwrap();
+        } else {
+            assert!(BASH_COLORS.contains_key(&color), "Unknown color: {color}");
+            write!(ret, ";{}", BASH_COLORS.get(&color).unwrap()).unwrap();
+        }
     }
 
     if !bgcolor.is_empty() {
-        assert!(
-            BASH_BGCOLORS.contains_key(&bgcolor),
-            "Unknown background color: {bgcolor}"
-        );
-        write!(ret, ";{}", BASH_BGCOLORS.get(&bgcolor).unwrap()).unwrap();
+        if let Some(code) = extended_color(bgcolor, "48") {
+            write!(ret, ";{code}").unwrap();
+        } else {
+            assert!(
+                BASH_BGCOLORS.contains_key(&bgcolor),
+                "Unknown background color: {bgcolor}"
+            );
+            write!(ret, ";{}", BASH_BGCOLORS.get(&bgcolor).unwrap()).unwrap();
+        }
     }
 
     ret.push('m');
     ret
 }
 
+/// Convert an extended (256-color or 24-bit) color spec to its SGR code
+///
+/// # Arguments
+///  * `spec`: The color spec, either `256:N` or `rgb:R,G,B`
+///  * `base`: The base SGR code, `38` for foreground or `48` for background
+///
+/// # Returns
+///   The SGR code for the color, or `None` if `spec` is not an extended color.
+///
+/// # Panics
+///   This function will panic if the spec has an extended prefix, but the
+///   values are not valid bytes.
+fn extended_color(spec: &str, base: &str) -> Option<String> {
+    if let Some(index) = spec.strip_prefix("256:") {
+        assert!(
+            index.parse::<u8>().is_ok(),
+            "Invalid 256-color index: {spec}"
+        );
+        return Some(format!("{base};5;{index}"));
+    }
+    if let Some(rgb) = spec.strip_prefix("rgb:") {
+        let channels: Vec<&str> = rgb.split(',').collect();
+        assert!(
+            channels.len() == 3 && channels.iter().all(|c| c.parse::<u8>().is_ok()),
+            "Invalid RGB color: {spec}"
+        );
+        return Some(format!("{base};2;{}", channels.join(";")));
+    }
+    None
+}
+
+/// Make text a hyperlink in terminals that support it
+///
+/// The link is an OSC 8 escape sequence. It is only added when colors are
+/// enabled, `NO_COLOR` is not set, and standard output is a terminal.
+///
+/// # Arguments
+///  * `url`: The URL to link to
+///  * `text`: The text to display
+///
+/// # Returns
+///   The text, wrapped in a hyperlink if hyperlinks are enabled.
+pub fn hyperlink(url: &str, text: &str) -> String {
+    let enabled = is_bash()
+        && !ANSI_UNSUPPORTED.load(Ordering::Relaxed)
+        && env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty())
+        && io::stdout().is_terminal();
+    link_text(url, text, enabled)
+}
+
+/// Wrap text in an OSC 8 hyperlink
+///
+/// # Arguments
+///  * `url`: The URL to link to
+///  * `text`: The text to display
+///  * `enabled`: Whether to add the hyperlink
+///
+/// # Returns
+///   The text, wrapped in a hyperlink if `enabled` is set.
+fn link_text(url: &str, text: &str, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b]8;;{url}\x1b\\{text}\x1b]8;;\x1b\\")
+    } else {
+        text.to_owned()
+    }
+}
+
 /// Check if running in a bash shell
 ///
 /// # Returns
@@ -162,6 +266,46 @@ fn keys_sorted_by_value(adict: &HashMap<&'static str, &'static str>) -> Vec<Stri
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_link_text() {
+        assert_eq!(
+            link_text("file:///tmp/0.out", "0.out", true),
+            "\x1b]8;;file:///tmp/0.out\x1b\\0.out\x1b]8;;\x1b\\"
+        );
+        assert_eq!(link_text("file:///tmp/0.out", "0.out", false), "0.out");
+    }
+
+    #[test]
+    fn test_extended_color_named() {
+        assert_eq!(extended_color("red", "38"), None);
+    }
+
+    #[test]
+    fn test_extended_color_256() {
+        assert_eq!(
+            extended_color("256:208", "38"),
+            Some(String::from("38;5;208"))
+        );
+        assert_eq!(
+            extended_color("256:17", "48"),
+            Some(String::from("48;5;17"))
+        );
+    }
+
+    #[test]
+    fn test_extended_color_rgb() {
+        assert_eq!(
+            extended_color("rgb:255,128,0", "38"),
+            Some(String::from("38;2;255;128;0"))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid RGB color")]
+    fn test_extended_color_bad_rgb() {
+        extended_color("rgb:255,128", "38");
+    }
+
     #[test]
     fn test_keys_sorted_by_value() {
         let mut data = HashMap::new();
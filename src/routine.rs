@@ -4,9 +4,36 @@
 * file, You can obtain one at https: //mozilla.org/MPL/2.0/.
 */
 //! Various routines used by the tool
-use std::{env, fs, io, io::Read as _, process::exit};
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Write as _,
+    fs,
+    io::{self, Read as _, Write as _},
+    ops::Deref,
+    path::Path,
+    sync::{
+        RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
-use crate::error::XorError;
+#[cfg(feature = "gzip")]
+use flate2::read::{MultiGzDecoder, ZlibDecoder};
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+#[cfg(feature = "tar")]
+use tar::{Builder, Header};
+
+use unicode_escape::decode;
+
+use crate::{args::LineEnding, cipher::XorKey, error::XorError};
+
+/// Line ending selected with `--line-ending`
+static LINE_ENDING: RwLock<LineEnding> = RwLock::new(LineEnding::Auto);
+
+/// Whether messages go to stderr, to keep stdout for the `--print0` keys
+static MESSAGES_TO_STDERR: AtomicBool = AtomicBool::new(false);
 
 /// Load from a file (or stdin)
 ///
@@ -25,35 +52,331 @@ pub fn load_file(filename: &str) -> Result<Vec<u8>, XorError> {
     Ok(fs::read(filename)?)
 }
 
+/// Bytes loaded from a file, either held in memory or memory-mapped
+#[derive(Debug)]
+pub enum FileData {
+    /// Data that was read into memory
+    Owned(Vec<u8>),
+    /// Data that is memory-mapped from a file
+    #[cfg(feature = "mmap")]
+    Mapped(Mmap),
+}
+
+impl Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Owned(data) => data,
+            #[cfg(feature = "mmap")]
+            Self::Mapped(data) => data,
+        }
+    }
+}
+
+/// Load from a file (or stdin) by memory-mapping it
+///
+/// This avoids holding a second copy of large files in memory. Standard
+/// input and empty files can't be mapped, so they are read normally instead.
+///
+/// # Safety Caveats
+///   The mapping is only valid while the file is unchanged. If another
+///   process modifies the file during analysis, the data seen by the tool
+///   can change underneath it, and truncating the file can cause the
+///   process to be killed (e.g. by `SIGBUS`).
+///
+/// # Arguments
+///   * `filename`: The name of the file to load from, or `-` to load
+///     from standard input
+///
+/// # Returns
+///   The bytes of the file, or standard input.
+///
+/// # Errors
+///   Returns `XorError::IO` if the file could not be opened or mapped.
+#[cfg(feature = "mmap")]
+pub fn load_file_mmap(filename: &str) -> Result<FileData, XorError> {
+    if filename == "-" {
+        return Ok(FileData::Owned(load_file(filename)?));
+    }
+    let file = fs::File::open(filename)?;
+    if file.metadata()?.len() == 0 {
+        return Ok(FileData::Owned(Vec::new()));
+    }
+    // SAFETY: The map is only ever read from, and the caveats about the
+    // file being modified while mapped are documented above.
+    let map = unsafe { Mmap::map(&file)? };
+    Ok(FileData::Mapped(map))
+}
+
+/// Decompress gzip or zlib compressed data
+///
+/// Data beginning with the gzip magic bytes is treated as gzip, anything
+/// else is treated as a zlib stream.
+///
+/// # Arguments
+///   * `data`: The compressed data
+///
+/// # Returns
+///   The decompressed data
+///
+/// # Errors
+///   Returns `XorError::IO` if the data could not be decompressed.
+#[cfg(feature = "gzip")]
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, XorError> {
+    let mut buf = Vec::new();
+    let result = if data.starts_with(&[0x1f, 0x8b]) {
+        MultiGzDecoder::new(data).read_to_end(&mut buf)
+    } else {
+        ZlibDecoder::new(data).read_to_end(&mut buf)
+    };
+    match result {
+        Ok(_) => Ok(buf),
+        Err(e) => Err(XorError::IO {
+            msg: format!("Failed to decompress input: {e}"),
+        }),
+    }
+}
+
+/// Destination for the decrypted plaintexts and the CSVs describing them
+pub enum Output {
+    /// Loose files in the output directory
+    Directory,
+    /// Entries in a tar archive
+    #[cfg(feature = "tar")]
+    Tar(Builder<fs::File>),
+}
+
+impl Output {
+    /// Create a tar archive to write the output into
+    ///
+    /// # Arguments
+    ///   * `filename`: The name of the archive to create
+    ///
+    /// # Returns
+    ///   Output that adds entries to the archive
+    ///
+    /// # Errors
+    ///   Returns `XorError::IO` if the archive could not be created
+    #[cfg(feature = "tar")]
+    pub fn tar(filename: &str) -> Result<Self, XorError> {
+        Ok(Self::Tar(Builder::new(fs::File::create(filename)?)))
+    }
+
+    /// Make sure the directory for output files exists
+    ///
+    /// Archives don't need directories, so nothing is created for them.
+    ///
+    /// # Arguments
+    ///   * `dirname`: The directory the output files are in
+    ///
+    /// # Errors
+    ///   Returns `XorError::Mkdir` if creating the directory failed
+    pub fn prepare_dir(&self, dirname: &str) -> Result<(), XorError> {
+        match self {
+            Self::Directory => mkdir(dirname),
+            #[cfg(feature = "tar")]
+            Self::Tar(_) => Ok(()),
+        }
+    }
+
+    /// Write a file to the output
+    ///
+    /// # Arguments
+    ///   * `path`: Path of the file. Used as the entry name in archives.
+    ///   * `data`: Contents of the file
+    ///
+    /// # Errors
+    ///   Returns `XorError::IO` if writing the file failed
+    pub fn write(&mut self, path: &str, data: &[u8]) -> Result<(), XorError> {
+        match self {
+            Self::Directory => fs::write(path, data)?,
+            #[cfg(feature = "tar")]
+            Self::Tar(builder) => {
+                let mut header = Header::new_gnu();
+                header.set_size(u64::try_from(data.len()).unwrap_or(u64::MAX));
+                header.set_mode(0o644);
+                // Tar paths always use forward slashes
+                builder.append_data(&mut header, path.replace('\\', "/"), data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a CSV file to the output
+    ///
+    /// When appending to a file in the output directory, the header is only
+    /// written if the file is new or empty. Archives are always written
+    /// whole.
+    ///
+    /// # Arguments
+    ///   * `path`: Path of the file. Used as the entry name in archives.
+    ///   * `header`: The header row of the CSV
+    ///   * `rows`: The rows of the CSV
+    ///   * `append`: Whether to append to an existing file
+    ///
+    /// # Errors
+    ///   Returns `XorError::IO` if writing the file failed
+    pub fn write_csv(
+        &mut self,
+        path: &str,
+        header: &[u8],
+        rows: &[u8],
+        append: bool,
+    ) -> Result<(), XorError> {
+        if append && matches!(self, Self::Directory) {
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            if file.metadata()?.len() == 0 {
+                file.write_all(header)?;
+            }
+            file.write_all(rows)?;
+            return Ok(());
+        }
+        self.write(path, &[header, rows].concat())
+    }
+
+    /// Finish writing the output
+    ///
+    /// # Errors
+    ///   Returns `XorError::IO` if the archive could not be completed
+    #[cfg_attr(
+        not(feature = "tar"),
+        expect(
+            clippy::unnecessary_wraps,
+            reason = "Finishing can only fail for tar archives."
+        )
+    )]
+    pub fn finish(self) -> Result<(), XorError> {
+        match self {
+            Self::Directory => Ok(()),
+            #[cfg(feature = "tar")]
+            Self::Tar(builder) => {
+                builder.into_inner()?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Get a `file://` URL for an output file
+    ///
+    /// # Arguments
+    ///   * `path`: Path of the file, as given to `write`
+    ///
+    /// # Returns
+    ///   The URL, or `None` if the file is not on disk
+    pub fn file_url(&self, path: &str) -> Option<String> {
+        match self {
+            Self::Directory => file_url(path),
+            #[cfg(feature = "tar")]
+            Self::Tar(_) => None,
+        }
+    }
+}
+
+/// Get a `file://` URL for a file
+///
+/// # Arguments
+///   * `path`: Path of the file
+///
+/// # Returns
+///   The URL of the absolute path of the file, or `None` if the file
+///   does not exist
+pub fn file_url(path: &str) -> Option<String> {
+    let absolute = fs::canonicalize(path).ok()?;
+    let absolute = absolute.to_str()?;
+    // Windows gives verbatim paths like \\?\C:\dir
+    let absolute = absolute.strip_prefix(r"\\?\").unwrap_or(absolute);
+    let mut url = String::from("file://");
+    if !absolute.starts_with('/') {
+        url.push('/');
+    }
+    for byte in absolute.bytes() {
+        match byte {
+            b'\\' => url.push('/'),
+            b'/' | b':' | b'-' | b'.' | b'_' | b'~' => url.push(char::from(byte)),
+            _ if byte.is_ascii_alphanumeric() => url.push(char::from(byte)),
+            _ => write!(url, "%{byte:02X}").unwrap(),
+        }
+    }
+    Some(url)
+}
+
+/// Marker file written into directories created by xortool
+///
+/// Only directories containing it (or empty ones) are removed by `rmdir`.
+const SENTINEL: &str = ".xortool";
+
 /// Create directory with the given name
 ///
+/// A `.xortool` marker file is written into newly created directories so
+/// they can be recognised when cleaning up.
+///
 /// # Arguments
 ///   * `dirname`: The name of the directory to create
 ///
 /// # Error
-///   creates `XorError::MkdirError` if creating the directory failed
+///   creates `XorError::Mkdir` if creating the directory failed
 pub fn mkdir(dirname: &str) -> Result<(), XorError> {
-    if fs::exists(dirname).unwrap() {
+    let to_error = |e: io::Error| XorError::Mkdir { msg: e.to_string() };
+    if fs::exists(dirname).map_err(to_error)? {
         Ok(())
     } else {
-        match fs::create_dir(dirname) {
-            Ok(()) => Ok(()),
-            Err(e) => Err(XorError::Mkdir { msg: e.to_string() }),
-        }
+        fs::create_dir(dirname)
+            .and_then(|()| fs::write(Path::new(dirname).join(SENTINEL), b""))
+            .map_err(to_error)
     }
 }
 
 /// Delete the given directory
 ///
+/// Symlinked directories are left untouched, and a directory that has
+/// already vanished is treated as deleted. Only directories created by
+/// `mkdir` (which contain its marker file), or ones holding nothing but
+/// files accepted by `is_known_file`, are deleted, so a user's own
+/// directory is never wiped. The second case covers directories left by
+/// releases that did not write the marker, and by the Python xortool.
+///
 /// # Arguments
 ///   * `dirname`: The name of the directory to delete
-pub fn rmdir(dirname: &str) {
-    let metadata = fs::symlink_metadata(dirname).unwrap();
-    let file_type = metadata.file_type();
-    if file_type.is_symlink() {
-        return; // do not clear link - we can get out of dir
+///   * `is_known_file`: Whether a file name is one xortool writes
+///
+/// # Errors
+///   creates `XorError::IO` if deleting the directory failed, or if it
+///   holds anything else and was not created by xortool
+pub fn rmdir(dirname: &str, is_known_file: fn(&str) -> bool) -> Result<(), XorError> {
+    let to_error = |e: io::Error| XorError::IO {
+        msg: format!("Can't remove directory {dirname}: {e}"),
+    };
+    let metadata = match fs::symlink_metadata(dirname) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(to_error(e)),
+    };
+    if metadata.file_type().is_symlink() {
+        return Ok(()); // do not clear link - we can get out of dir
+    }
+    let created_by_us = fs::exists(Path::new(dirname).join(SENTINEL)).map_err(to_error)?;
+    if !created_by_us {
+        for entry in fs::read_dir(dirname).map_err(to_error)? {
+            let entry = entry.map_err(to_error)?;
+            let known = !entry.file_type().map_err(to_error)?.is_dir()
+                && entry.file_name().to_str().is_some_and(is_known_file);
+            if !known {
+                return Err(XorError::IO {
+                    msg: format!(
+                        "Refusing to remove directory {dirname}: it is not empty and was not created by xortool"
+                    ),
+                });
+            }
+        }
+    }
+    match fs::remove_dir_all(dirname) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(to_error(e)),
+        _ => Ok(()),
     }
-    fs::remove_dir_all(dirname).unwrap();
 }
 
 /// Decode a string of hexadecimal values into their values
@@ -66,7 +389,10 @@ pub fn rmdir(dirname: &str) {
 ///
 /// # Returns
 ///   Vector of the bytes of the decoded text.
-pub fn decode_from_hex(text: &[u8]) -> Vec<u8> {
+///
+/// # Errors
+///   Returns `XorError::UnicodeDecode` if there is an odd number of hex digits
+pub fn decode_from_hex(text: &[u8]) -> Result<Vec<u8>, XorError> {
     // FIXME: Can probably make this a lot cleaner
     let mut only_hex_digits = Vec::new();
     for &character in text {
@@ -74,18 +400,144 @@ pub fn decode_from_hex(text: &[u8]) -> Vec<u8> {
             only_hex_digits.push(character);
         }
     }
+    if only_hex_digits.len() % 2 != 0 {
+        return Err(XorError::UnicodeDecode {
+            msg: format!(
+                "Odd number of hex digits ({}) in the input",
+                only_hex_digits.len()
+            ),
+        });
+    }
     let mut result = Vec::new();
-    assert_eq!(
-        only_hex_digits.len() % 2,
-        0,
-        "Odd number of characters after extracting only hex digits."
-    );
     for chunk in only_hex_digits.chunks_exact(2) {
         let chunk_str = String::from_utf8(chunk.to_vec()).unwrap();
 
         result.push(u8::from_str_radix(&chunk_str, 16).unwrap());
     }
-    result
+    Ok(result)
+}
+
+/// Decode a `hexdump -C` or `xxd` style dump into the bytes it shows
+///
+/// Each line starts with an offset, followed by the hex bytes and an ASCII
+/// gutter. The offset and gutter are ignored, so hex-looking characters in
+/// them don't end up in the output. A `*` line (used by `hexdump` for
+/// repeated lines) repeats the previous line up to the next offset.
+///
+/// # Arguments
+///   * `text`: The bytes of the dump to decode
+///
+/// # Returns
+///   Vector of the bytes shown in the dump
+///
+/// # Errors
+///   Returns `XorError::UnicodeDecode` if an offset or hex byte is invalid
+pub fn decode_from_hexdump(text: &[u8]) -> Result<Vec<u8>, XorError> {
+    let mut result = Vec::new();
+    let mut previous_line: Vec<u8> = Vec::new();
+    let mut repeating = false;
+    for line in String::from_utf8_lossy(text).lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "*" {
+            repeating = true;
+            continue;
+        }
+        let (offset, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        // xxd puts a colon after the offset, and separates the gutter with two spaces
+        let xxd_style = offset.ends_with(':');
+        let offset = usize::from_str_radix(offset.trim_end_matches(':'), 16).map_err(|e| {
+            XorError::UnicodeDecode {
+                msg: format!("Bad hexdump offset '{offset}': {e}"),
+            }
+        })?;
+        if repeating && !previous_line.is_empty() {
+            while result.len() < offset {
+                result.extend_from_slice(&previous_line);
+            }
+            result.truncate(offset);
+        }
+        repeating = false;
+
+        let rest = rest.trim_start();
+        let hex_area = if xxd_style {
+            rest.split("  ").next()
+        } else {
+            rest.split('|').next()
+        }
+        .unwrap_or_default();
+        let mut line_bytes = Vec::new();
+        for group in hex_area.split_whitespace() {
+            if !group.is_ascii() || group.len() % 2 != 0 {
+                return Err(XorError::UnicodeDecode {
+                    msg: format!("Bad hexdump bytes '{group}'"),
+                });
+            }
+            for pair in group.as_bytes().chunks(2) {
+                let pair = String::from_utf8_lossy(pair);
+                line_bytes.push(u8::from_str_radix(&pair, 16).map_err(|e| {
+                    XorError::UnicodeDecode {
+                        msg: format!("Bad hexdump byte '{pair}': {e}"),
+                    }
+                })?);
+            }
+        }
+        result.extend_from_slice(&line_bytes);
+        previous_line = line_bytes;
+    }
+    Ok(result)
+}
+
+/// Decode text with `\xNN` and other backslash escapes into bytes
+///
+/// Escapes of code points up to `\xFF` become a single byte, and other
+/// escaped code points are UTF-8 encoded. Everything that is not part of an
+/// escape, including non-ASCII text, is kept as it is. Line breaks at the
+/// end of the text are ignored, since they are usually not part of the
+/// pasted data.
+///
+/// # Arguments
+///   * `text`: The bytes of the escaped text to decode
+///
+/// # Returns
+///   Vector of the decoded bytes
+///
+/// # Errors
+///   Returns `XorError::UnicodeDecode` if the text has a malformed escape
+pub fn decode_from_escaped(text: &[u8]) -> Result<Vec<u8>, XorError> {
+    let end = text
+        .iter()
+        .rposition(|byte| !matches!(byte, b'\r' | b'\n'))
+        .map_or(0, |last| last + 1);
+    let mut result = Vec::with_capacity(end);
+    for chunk in text[..end].utf8_chunks() {
+        // Escapes are ASCII, so only the ASCII runs need decoding
+        for piece in chunk
+            .valid()
+            .split_inclusive(|character: char| !character.is_ascii())
+        {
+            let ascii_len = piece
+                .char_indices()
+                .find(|(_, character)| !character.is_ascii())
+                .map_or(piece.len(), |(index, _)| index);
+            let (ascii, literal) = piece.split_at(ascii_len);
+            let decoded = decode(ascii).map_err(|e| XorError::UnicodeDecode {
+                msg: format!("Bad escape in input: {e}"),
+            })?;
+            for character in decoded.chars() {
+                if let Ok(byte) = u8::try_from(character) {
+                    result.push(byte);
+                } else {
+                    result.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+            }
+            result.extend_from_slice(literal.as_bytes());
+        }
+        result.extend_from_slice(chunk.invalid());
+    }
+    Ok(result)
 }
 
 /// Reverse xor encryption on a set of bytes
@@ -97,25 +549,103 @@ pub fn decode_from_hex(text: &[u8]) -> Vec<u8> {
 /// # Returns
 ///   Decrypted bytes
 pub fn dexor(text: &[u8], key: &[u8]) -> Vec<u8> {
-    let val_mod = key.len();
-    text.iter()
-        .enumerate()
-        .map(|(idx, chr)| key[idx % val_mod] ^ chr)
-        .collect()
+    XorKey::from(key).cycle_xor(text)
 }
 
-/// Exit the program and display the given error message
+/// Compute the Shannon entropy of a set of bytes
 ///
 /// # Arguments
-///   * `exit_message`: The message to display on exit.
-///   * `exit_code`: The exit code to exit the program with.
-pub fn die(exit_message: &str, exit_code: i32) {
-    if env::consts::OS == "windows" {
-        println!("{exit_message}\r");
-    } else {
-        println!("{exit_message}");
+///   * `text`: The bytes to compute the entropy of
+///
+/// # Returns
+///   Byte-level Shannon entropy of the data, in bits per byte (0.0 to 8.0)
+pub fn entropy(text: &[u8]) -> f64 {
+    let mut counts: HashMap<u8, usize> = HashMap::new();
+    for &byte in text {
+        *counts.entry(byte).or_default() += 1;
+    }
+    entropy_from_counts(counts.into_values())
+}
+
+/// Compute the Shannon entropy from the number of times each byte occurs
+///
+/// # Arguments
+///   * `counts`: How many times each distinct byte occurs
+///
+/// # Returns
+///   Shannon entropy in bits per byte (0.0 to 8.0), or 0.0 if there are no bytes
+pub fn entropy_from_counts<I: IntoIterator<Item = usize>>(counts: I) -> f64 {
+    let counts: Vec<usize> = counts.into_iter().filter(|&count| count > 0).collect();
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    #[expect(
+        clippy::cast_precision_loss,
+        clippy::as_conversions,
+        reason = "Need to convert to float for division."
+    )]
+    let total = total as f64;
+    counts
+        .iter()
+        .map(|&count| {
+            #[expect(
+                clippy::cast_precision_loss,
+                clippy::as_conversions,
+                reason = "Need to convert to float for division."
+            )]
+            let probability = count as f64 / total;
+            probability * (1.0 / probability).log2()
+        })
+        .sum()
+}
+
+/// Select the line ending returned by `line_end`
+///
+/// # Arguments
+///   * `ending`: The line ending to use from now on
+pub fn set_line_ending(ending: LineEnding) {
+    *LINE_ENDING.write().unwrap() = ending;
+}
+
+/// Get the line ending to use for output
+///
+/// # Returns
+///   The line ending selected with `set_line_ending`, or the OS default
+pub fn line_end() -> &'static str {
+    line_end_for(*LINE_ENDING.read().unwrap(), env::consts::OS)
+}
+
+/// Set whether messages are printed to stderr instead of stdout
+///
+/// # Arguments
+///   * `to_stderr`: True to keep stdout free for machine readable output
+pub fn set_messages_to_stderr(to_stderr: bool) {
+    MESSAGES_TO_STDERR.store(to_stderr, Ordering::Relaxed);
+}
+
+/// Check whether messages are printed to stderr instead of stdout
+///
+/// # Returns
+///   The value set with `set_messages_to_stderr`, false by default
+pub fn messages_to_stderr() -> bool {
+    MESSAGES_TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// Get the line ending to use on an operating system
+///
+/// # Arguments
+///   * `ending`: The selected line ending
+///   * `os`: The name of the operating system, as in `env::consts::OS`
+///
+/// # Returns
+///   The line ending characters
+fn line_end_for(ending: LineEnding, os: &str) -> &'static str {
+    match ending {
+        LineEnding::Crlf => "\r\n",
+        LineEnding::Auto if os == "windows" => "\r\n",
+        LineEnding::Lf | LineEnding::Auto => "\n",
     }
-    exit(exit_code);
 }
 
 // `is_linux` and `alphanum` in the original source are dead code.
@@ -132,11 +662,130 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn test_decompress_gzip() {
+        let data = load_file("tests/small_file.txt.gz").unwrap();
+        assert_eq!(decompress(&data).unwrap(), "Hello World!".as_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn test_decompress_invalid() {
+        let result = decompress("Hello World!".as_bytes());
+        assert!(
+            matches!(result, Err(XorError::IO { msg }) if msg.starts_with("Failed to decompress input"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn test_load_file_mmap() {
+        assert_eq!(
+            &*load_file_mmap("tests/small_file.txt").unwrap(),
+            "Hello World!".as_bytes()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tar")]
+    fn test_output_tar() {
+        let filename = "tests/output_test.tar";
+        let mut output = Output::tar(filename).unwrap();
+        output.prepare_dir("xortool_out").unwrap();
+        output.write("xortool_out/0.out", b"Hello World!").unwrap();
+        output
+            .write("xortool_out/filename-key.csv", b"file_name;key_repr")
+            .unwrap();
+        output.finish().unwrap();
+
+        let mut archive = tar::Archive::new(fs::File::open(filename).unwrap());
+        let mut entry = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(entry.path().unwrap().to_str(), Some("xortool_out/0.out"));
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, b"Hello World!");
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn test_file_url() {
+        let url = file_url("tests/small_file.txt").unwrap();
+        assert!(url.starts_with("file:///"), "{url}");
+        assert!(url.ends_with("/tests/small_file.txt"), "{url}");
+        assert_eq!(file_url("tests/missing.bin"), None);
+    }
+
     #[test]
     fn test_mkdir_already_exists() {
         assert_eq!(mkdir("src"), Ok(()))
     }
 
+    /// Output files recognised by the `rmdir` tests
+    fn is_test_output(name: &str) -> bool {
+        Path::new(name).extension().is_some_and(|ext| ext == "out") || name == "report.txt"
+    }
+
+    #[test]
+    fn test_rmdir_vanished() {
+        let dirname = "tests/rmdir_vanished";
+        mkdir(dirname).unwrap();
+        fs::remove_dir_all(dirname).unwrap();
+        assert_eq!(rmdir(dirname, is_test_output), Ok(()));
+    }
+
+    #[test]
+    fn test_rmdir_existing() {
+        let dirname = "tests/rmdir_existing";
+        mkdir(dirname).unwrap();
+        assert!(fs::exists(format!("{dirname}/{SENTINEL}")).unwrap());
+        fs::write(format!("{dirname}/file.txt"), "data").unwrap();
+        assert_eq!(rmdir(dirname, is_test_output), Ok(()));
+        assert!(!fs::exists(dirname).unwrap());
+    }
+
+    #[test]
+    fn test_rmdir_empty() {
+        let dirname = "tests/rmdir_empty";
+        fs::create_dir(dirname).unwrap();
+        assert_eq!(rmdir(dirname, is_test_output), Ok(()));
+        assert!(!fs::exists(dirname).unwrap());
+    }
+
+    #[test]
+    fn test_rmdir_known_outputs() {
+        let dirname = "tests/rmdir_known_outputs";
+        fs::create_dir(dirname).unwrap();
+        fs::write(format!("{dirname}/0.out"), "data").unwrap();
+        fs::write(format!("{dirname}/report.txt"), "data").unwrap();
+        let result = rmdir(dirname, is_test_output);
+        let still_exists = fs::exists(dirname).unwrap();
+        if still_exists {
+            fs::remove_dir_all(dirname).unwrap();
+        }
+        assert_eq!(result, Ok(()));
+        assert!(!still_exists);
+    }
+
+    #[test]
+    fn test_rmdir_foreign() {
+        let dirname = "tests/rmdir_foreign";
+        fs::create_dir(dirname).unwrap();
+        fs::write(format!("{dirname}/file.txt"), "data").unwrap();
+        let result = rmdir(dirname, is_test_output);
+        let still_exists = fs::exists(format!("{dirname}/file.txt")).unwrap();
+        fs::remove_dir_all(dirname).unwrap();
+        assert_eq!(
+            result,
+            Err(XorError::IO {
+                msg: format!(
+                    "Refusing to remove directory {dirname}: it is not empty and was not created by xortool"
+                )
+            })
+        );
+        assert!(still_exists);
+    }
+
     #[test]
     fn test_mkdir_error() {
         let result = mkdir("src/hello/world");
@@ -153,7 +802,96 @@ mod tests {
     #[test]
     fn test_decode_from_hex() {
         let input = "48 65 6C 6C 6F 20 57 6F 72 6C 64".as_bytes();
-        assert_eq!(decode_from_hex(input), "Hello World".as_bytes());
+        assert_eq!(decode_from_hex(input).unwrap(), "Hello World".as_bytes());
+    }
+
+    #[test]
+    fn test_decode_from_hex_odd_length() {
+        assert_eq!(
+            decode_from_hex(b"abc"),
+            Err(XorError::UnicodeDecode {
+                msg: String::from("Odd number of hex digits (3) in the input")
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_from_escaped() {
+        let escaped = fs::read("tests/escaped.txt").unwrap();
+        assert_eq!(
+            decode_from_escaped(&escaped).unwrap(),
+            b"Hello\x00\xff\n\tWorld \xc3\xa9"
+        );
+        assert_eq!(
+            decode_from_escaped(b"\\x41\xff\\u20ac").unwrap(),
+            b"A\xff\xe2\x82\xac"
+        );
+        for bad in [&b"\\x4"[..], b"\\q", b"ab\\"] {
+            assert!(matches!(
+                decode_from_escaped(bad),
+                Err(XorError::UnicodeDecode { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_entropy_all_zeros() {
+        assert!(entropy(&[0; 64]).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_entropy_uniform() {
+        let data: Vec<u8> = (0..=255).cycle().take(1024).collect();
+        assert!((entropy(&data) - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_entropy_two_symbols() {
+        assert!((entropy("abab".as_bytes()) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_entropy_from_counts() {
+        assert!((entropy_from_counts([2, 2, 0]) - 1.0).abs() < 1e-9);
+        assert!(entropy_from_counts([]).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_line_end_auto() {
+        assert_eq!(line_end_for(LineEnding::Auto, "windows"), "\r\n");
+        assert_eq!(line_end_for(LineEnding::Auto, "linux"), "\n");
+    }
+
+    #[test]
+    fn test_line_end_forced() {
+        assert_eq!(line_end_for(LineEnding::Lf, "windows"), "\n");
+        assert_eq!(line_end_for(LineEnding::Crlf, "linux"), "\r\n");
+    }
+
+    #[test]
+    fn test_decode_from_hexdump_canonical() {
+        let dump = fs::read("tests/hexdump_c.txt").unwrap();
+        assert_eq!(
+            decode_from_hexdump(&dump).unwrap(),
+            fs::read("tests/hexdump.bin").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decode_from_hexdump_xxd() {
+        let dump = fs::read("tests/xxd.txt").unwrap();
+        assert_eq!(
+            decode_from_hexdump(&dump).unwrap(),
+            fs::read("tests/hexdump.bin").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decode_from_hexdump_invalid() {
+        assert!(matches!(
+            decode_from_hexdump(b"00000000  4g 65  |He|"),
+            Err(XorError::UnicodeDecode { .. })
+        ));
     }
 
     #[test]
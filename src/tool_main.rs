@@ -4,713 +4,5063 @@
 * file, You can obtain one at https: //mozilla.org/MPL/2.0/.
 */
 //! Core logic for xortool
-use clap::Parser as _;
+use clap::{CommandFactory as _, Parser as _, ValueEnum as _};
+use clap_complete::generate;
 
 use crate::{
-    colors::{C_BEST_KEYLEN, C_BEST_PROB, C_FATAL, C_KEYLEN, C_PROB},
+    colors::{C_BEST_KEYLEN, C_BEST_PROB, C_FATAL, C_KEYLEN, C_PROB, hyperlink},
     error::XorError,
     routine::{dexor, mkdir},
 };
 
 use std::{
     ascii::escape_default,
+    cmp::Reverse,
     collections::{HashMap, hash_map::Entry},
     env,
+    ffi::OsString,
     fmt::Write as _,
     fs,
-    io::Write as _,
-    path::MAIN_SEPARATOR,
-    process::exit,
+    io::{self, Write as _},
+    path::{MAIN_SEPARATOR, Path},
+    process::ExitCode,
+    time::{Duration, Instant},
 };
 
+/// Print a message to stdout, or to stderr while stdout is kept for the
+/// `--print0` keys
+macro_rules! message {
+    ($($arg:tt)*) => {
+        if messages_to_stderr() {
+            eprint!($($arg)*);
+        } else {
+            print!($($arg)*);
+        }
+    };
+}
+
+/// Version of the tools
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Version plus the build target and enabled features, shown by `--version`
+pub const BUILD_INFO: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\ntarget: ",
+    env!("XORTOOL_TARGET"),
+    "\nfeatures: ",
+    env!("XORTOOL_FEATURES"),
+);
+
+/// Ranked (key length, fitness) candidates
+type Fitnesses = Vec<(i32, f64)>;
+
+/// Probable keys in the order they were found, and the char each was found by
+type ProbableKeys = (Vec<Vec<u8>>, HashMap<Vec<u8>, u8>);
+
+/// Most frequent character assumed by `--auto-char`
+const AUTO_CHAR: u8 = b' ';
+
 // TODO: Support changing with CLI arg
 /// Directory to put decrypted data in
 const DIRNAME: &str = "xortool_out";
 
+// this is split up in two files since the
+// key can contain all kinds of characters
+/// CSV mapping the output files to their keys
+const KEY_MAPPING_CSV: &str = "filename-key.csv";
+/// CSV mapping the output files to the char used and their score
+const PERC_MAPPING_CSV: &str = "filename-char_used-perc_valid.csv";
+/// Summary of the run written by `--report`
+const REPORT_TXT: &str = "report.txt";
+
+#[cfg(feature = "logging")]
+use log::{debug, trace};
+
+#[cfg(feature = "gzip")]
+use crate::routine::decompress;
+#[cfg(feature = "mmap")]
+use crate::routine::load_file_mmap;
 use crate::{
-    args::Parameters,
-    charset::PREDEFINED_CHARSETS,
+    args::{Endian, KeyFormat, KeyLang, Parameters, Score},
+    charset::{PREDEFINED_CHARSETS, charset_listing},
     colors::{C_COUNT, C_DIV, C_KEY, C_RESET, C_WARN},
-    routine::{decode_from_hex, die, load_file, rmdir},
+    routine::{
+        FileData, Output, decode_from_escaped, decode_from_hex, decode_from_hexdump, entropy,
+        entropy_from_counts, line_end, load_file, messages_to_stderr, rmdir, set_line_ending,
+        set_messages_to_stderr,
+    },
 };
 
+/// Default factor the fitness of `--prefer-length` lengths is multiplied by
+const DEFAULT_PREFER_BOOST: f64 = 1.1;
+
+/// Entropy (in bits/byte) above which the input is likely not simple XOR
+const ENTROPY_WARN_THRESHOLD: f64 = 7.5;
+
+/// Largest key length probed when `-m` is not given
+const DEFAULT_MAX_KEY_LENGTH: i32 = 65;
+
+/// Fraction of the best agreement rate a shorter key length must reach to be preferred
+const AGREEMENT_TOLERANCE: f64 = 0.9;
+
+/// Fraction of the highest autocorrelation peak a shorter peak must reach to be preferred
+const AUTOCORR_TOLERANCE: f64 = 0.5;
+
+/// Number of most common divisors printed unless `--all-divisors` is given
+const MAX_SHOWN_DIVISORS: usize = 3;
+
 /// Main function for xortool
 ///
+/// Errors exit with code 1, like the Python xortool. With
+/// `--detailed-exit-codes`, each kind of error gets its own exit code
+/// instead (see `XorError::exit_code`). Running out of `--timeout` always
+/// exits with `EXIT_TIMEOUT`.
+///
 /// # Arguments
 ///   * `args`: Optional vector of arguments to parse. If not supplied,
 ///     arguments are read from the command line instead.
-pub fn main(args: Option<Vec<String>>) {
-    let result = main_inner(args);
-    let line_end = if env::consts::OS == "windows" {
-        "\r\n"
-    } else {
-        "\n"
+///
+/// # Returns
+///   The exit code for the process
+pub fn main(args: Option<Vec<String>>) -> ExitCode {
+    let args: Vec<OsString> = match args {
+        Some(a) => a.into_iter().map(OsString::from).collect(),
+        None => env::args_os().collect(),
+    };
+    // FIXME: Move back to using parse() so we get clap's native error handling
+    // This will require bumping to version 2.0, since it will lead to us not
+    // matching the original source.
+    let (result, detailed_exit_codes) = match Parameters::try_parse_from(&args) {
+        Ok(param) => {
+            let detailed_exit_codes = param.detailed_exit_codes;
+            (main_inner(param), detailed_exit_codes)
+        }
+        // There are no parameters to check, so look for the flag directly
+        Err(e) => (Err(XorError::from(e)), requests_detailed_exit_codes(&args)),
     };
+    let line_end = line_end();
     match result {
-        Ok(()) => (),
+        Ok(code) => code,
         Err(e) => {
-            print!("{}{e}{}{line_end}", *C_FATAL, *C_RESET);
-            exit(1)
+            message!("{}{e}{}{line_end}", *C_FATAL, *C_RESET);
+            // A timeout is not a failure of the analysis, so it always gets its own code
+            if detailed_exit_codes || matches!(e, XorError::Timeout { .. }) {
+                ExitCode::from(e.exit_code())
+            } else {
+                ExitCode::FAILURE
+            }
         }
     }
 }
 
+/// Check whether a command line that failed to parse asked for detailed exit codes
+///
+/// Arguments after `--` are filenames, so they are not checked.
+///
+/// # Arguments
+///   * `args`: The command line, including the program name
+///
+/// # Returns
+///   Whether `--detailed-exit-codes` is given before any `--`
+fn requests_detailed_exit_codes(args: &[OsString]) -> bool {
+    args.iter()
+        .skip(1)
+        .map(OsString::as_os_str)
+        .take_while(|&arg| arg != "--")
+        .any(|arg| arg == "--detailed-exit-codes")
+}
+
 /// Inner logic of the main function for xortool
 ///
 /// # Arguments
-///   * `args`: Optional vector of arguments to parse. If not supplied,
-///     arguments are read from the command line instead.
+///   * `param`: The parsed command line arguments
+///
+/// # Returns
+///   The exit code for the process
 ///
 /// # Errors
 ///   Returns any errors that occurred during tool execution
-fn main_inner(args: Option<Vec<String>>) -> Result<(), XorError> {
-    // FIXME: Move back to using parse() so we get clap's native error handling
-    // This will require bumping to version 2.0, since it will lead to us not
-    // matching the original source.
-    let mut param = match args {
-        Some(a) => Parameters::try_parse_from(a),
-        None => Parameters::try_parse(),
-    }?;
+fn main_inner(mut param: Parameters) -> Result<ExitCode, XorError> {
+    set_line_ending(param.line_ending);
+    set_messages_to_stderr(param.print0);
+    if print_listings(&param) {
+        return Ok(ExitCode::SUCCESS);
+    }
+    let line_end = line_end();
+    param.deadline = param
+        .timeout
+        .and_then(|timeout| Instant::now().checked_add(timeout));
 
     let ciphertext = get_ciphertext(&param)?;
-    if param.known_key_length.is_none() {
-        param.known_key_length = Some(guess_key_length(&ciphertext, &param)?);
+    if let Some(pct) = param.keylen_pct {
+        param.key_lengths = vec![key_length_from_pct(ciphertext.len(), pct)?];
+    }
+    let window = analysis_window(&ciphertext, &param)?;
+    let sample = key_length_sample(window, &param);
+    if param.max_key_length.is_none() {
+        param.max_key_length = Some(default_max_key_length(sample.len()));
+    }
+    if let Some(top_n) = param.histogram {
+        print_histogram(window, top_n);
+        return Ok(ExitCode::SUCCESS);
+    }
+    if warn_if_constant(sample, &param) {
+        return Ok(ExitCode::FAILURE);
+    }
+    let mut timings = PhaseTimings::default();
+    let key_length_start = Instant::now();
+    let (key_lengths, fitnesses) = select_key_lengths(sample, &param)?;
+    timings.key_length += key_length_start.elapsed();
+    check_deadline(&param, || String::from("Key length guessing finished"))?;
+    if param.echo_params {
+        message!("{}", echo_params(&key_lengths, &param));
+    }
+    if param.only_key_length {
+        return Ok(ExitCode::SUCCESS);
     }
+    let report = param
+        .report
+        .then(|| report_header(&fitnesses, &key_lengths, &param));
 
-    let try_chars: Vec<u8> = if param.brute_chars {
-        (0..=255).collect()
-    } else if param.brute_printable {
-        PREDEFINED_CHARSETS
-            .get("printable")
-            .unwrap()
-            .bytes()
-            .collect()
-    } else if param.most_frequent_char.is_some() {
-        vec![param.most_frequent_char.unwrap()]
-    } else {
-        die(
-            &format!(
-                "{}Most possible char is needed to guess the key!{}",
-                *C_WARN, *C_RESET
-            ),
-            1,
-        );
-        // This is never actually hit, as die() causes termination
-        Vec::new()
+    let Some(try_chars) = announce_most_frequent_chars(&param) else {
+        return Ok(ExitCode::FAILURE);
     };
 
-    let (probable_keys, key_char_used) =
-        guess_probable_keys_for_chars(&ciphertext, &try_chars, &param);
+    if let Some(count) = param.scan_lengths {
+        let scan = scan_key_lengths(
+            &ciphertext,
+            window,
+            &fitnesses,
+            count,
+            &try_chars,
+            &mut param,
+        )?;
+        message!("{}", length_scan_table(&scan));
+        return Ok(ExitCode::SUCCESS);
+    }
 
-    print_keys(&probable_keys);
-    produce_plaintext(&ciphertext, &probable_keys, &key_char_used, &param)?;
+    let (mut output, base_dir) = open_output(&param)?;
 
-    // FIXME: Need Exception handling. Needs to be bubbled up from functions instead of them panicking.
-    // cleanup();
-    Ok(())
+    for (done, &key_length) in key_lengths.iter().enumerate() {
+        check_deadline(&param, || {
+            format!("Finished {done} of {} key lengths", key_lengths.len())
+        })?;
+        param.known_key_length = Some(key_length);
+        // When trying several key lengths, each one gets its own directory.
+        let out_dir = if key_lengths.len() > 1 {
+            let out_dir = format!("{base_dir}{MAIN_SEPARATOR}length_{key_length}");
+            message!("Key length {key_length} (files in {out_dir}):{line_end}");
+            out_dir
+        } else {
+            base_dir.clone()
+        };
+
+        print_column_diagnostics(window, key_length, &param);
+        let guessing_start = Instant::now();
+        let (probable_keys, key_char_used) =
+            guess_keys_for_length(&ciphertext, window, &try_chars, &param)?;
+        timings.key_guessing += guessing_start.elapsed();
+
+        print_key_details(&ciphertext, &probable_keys, &param)?;
+        let plaintext_start = Instant::now();
+        produce_plaintext(
+            &ciphertext,
+            &probable_keys,
+            &key_char_used,
+            &param,
+            &out_dir,
+            report.as_deref(),
+            &mut output,
+        )?;
+        timings.plaintext += plaintext_start.elapsed();
+    }
+    output.finish()?;
+    if param.benchmark {
+        eprint!("{}", timings.breakdown());
+    }
+    Ok(ExitCode::SUCCESS)
 }
 
-/// Read in the encrypted data
+/// Check whether the analysis has run past its `--timeout`
 ///
 /// # Arguments
 ///   * `param`: Command line parameters supplied to the tool
+///   * `progress`: Builds the summary of how far the analysis got
 ///
-/// # Returns
-///   The bytes of the encrypted data.
-fn get_ciphertext(param: &Parameters) -> Result<Vec<u8>, XorError> {
-    let ciphertext = load_file(&param.filename)?;
-
-    if param.input_is_hex {
-        return Ok(decode_from_hex(&ciphertext));
+/// # Errors
+///   Returns `XorError::Timeout` with the summary if the deadline has passed
+fn check_deadline(param: &Parameters, progress: impl FnOnce() -> String) -> Result<(), XorError> {
+    match (param.deadline, param.timeout) {
+        (Some(deadline), Some(timeout)) if Instant::now() >= deadline => Err(XorError::Timeout {
+            msg: format!(
+                "Gave up after {} seconds. {}",
+                timeout.as_secs_f64(),
+                progress()
+            ),
+        }),
+        _ => Ok(()),
     }
-    Ok(ciphertext)
 }
 
-// -----------------------------------------------------------------------------
-// KEYLENGTH GUESSING SECTION
-// -----------------------------------------------------------------------------
+/// Time spent in each phase of the analysis, for `--benchmark`
+#[derive(Debug, Default)]
+struct PhaseTimings {
+    /// Time spent guessing the key length
+    key_length: Duration,
+    /// Time spent guessing the keys, over all key lengths
+    key_guessing: Duration,
+    /// Time spent producing the plaintexts, over all key lengths
+    plaintext: Duration,
+}
 
-/// Guess the length of the key used to encrypt the data
+impl PhaseTimings {
+    /// Format the time spent in each phase
+    ///
+    /// # Returns
+    ///   One line per phase, with its name and how long it took in milliseconds
+    fn breakdown(&self) -> String {
+        let line_end = line_end();
+        let mut breakdown = format!("Timings:{line_end}");
+        for (phase, duration) in [
+            ("key length guessing", self.key_length),
+            ("key guessing", self.key_guessing),
+            ("plaintext production", self.plaintext),
+        ] {
+            let millis = duration.as_secs_f64() * 1000.0;
+            write!(breakdown, "  {phase:>20}: {millis:.3} ms{line_end}").unwrap();
+        }
+        breakdown
+    }
+}
+
+/// Print the guessed keys, and the reports asked for about them
 ///
 /// # Arguments
-///   * `text`: The encrypted data
-///   * `param`: Command line parameters provided to the tool
-///
-/// # Returns
-///   The guessed length of the key used to encrypt the data.
+///   * `ciphertext`: The encrypted data
+///   * `probable_keys`: The keys guessed for the current key length
+///   * `param`: Command line parameters supplied to the tool
 ///
-/// # Error
-///   Returns `AnalysisError` if no candidates could be found for key length.
-fn guess_key_length(text: &[u8], param: &Parameters) -> Result<i32, XorError> {
-    let mut fitnesses = calculate_fitnesses(text, param);
-    if fitnesses.is_empty() {
-        return Err(XorError::Analysis {
-            msg: String::from("No candidates for key length found! Too small file?"),
-        });
+/// # Errors
+///   Returns any error from printing the keys or the comparison
+fn print_key_details(
+    ciphertext: &[u8],
+    probable_keys: &[Vec<u8>],
+    param: &Parameters,
+) -> Result<(), XorError> {
+    // Listing the keys for scripts is what --print0 is for, so it ignores --quiet
+    if !param.quiet || param.print0 {
+        print_keys(probable_keys, param)?;
     }
-    // Sorting here instead of inside the print_fitnesses function since
-    // in Python, the list was passed by reference and thus sorted for all
-    // later functions. But here we pass a immutable slice, so if we sorted
-    // in the function, it would only apply to that function
-    fitnesses.sort_by(|a, b| a.1.total_cmp(&b.1));
-    fitnesses.reverse();
-
-    print_fitnesses(&fitnesses);
-    guess_and_print_divisors(&fitnesses, param);
-    Ok(get_max_fitnessed_key_length(&fitnesses))
+    if param.charset_report {
+        print_charset_report(ciphertext, probable_keys, param);
+    }
+    if let Some(&[index_a, index_b]) = param.compare.as_deref() {
+        print_comparison(ciphertext, probable_keys, index_a, index_b)?;
+    }
+    Ok(())
 }
 
-/// Calculate fitness of different key lengths
+/// Guess the keys for the current key length
+///
+/// The keys are guessed from the analyzed window, then aligned to the start
+/// of the ciphertext and filtered and sorted as asked.
 ///
 /// # Arguments
-///   * `text`: The encrypted data to decode
-///   * `param`: The command line parameters passed to the tool
+///   * `ciphertext`: The encrypted data
+///   * `window`: The part of the encrypted data being analyzed
+///   * `try_chars`: Characters to try as the most common character
+///   * `param`: Command line parameters supplied to the tool
 ///
 /// # Returns
-///   Vector of tuples. For each tuple, the first element is a key
-///   length, and the second is the fitness of that key length
-fn calculate_fitnesses(text: &[u8], param: &Parameters) -> Vec<(i32, f64)> {
-    let mut prev = 0.0;
-    let mut pprev = 0.0;
-    let mut fitnesses = Vec::new();
-
-    let max_key_len = param.max_key_length.unwrap_or_default();
-
-    let range_end = match param.max_key_length {
-        Some(i) => i + 1,
-        None => 0,
-    };
-
-    let mut outer_key_len = 0;
-
-    for key_length in 1..range_end {
-        let fitness = f64::from(count_equals(text, key_length));
-
-        let fitness = fitness / (f64::from(max_key_len) + (f64::from(key_length)).powf(1.5));
-
-        if pprev < prev && prev > fitness {
-            // Local maximum
-            fitnesses.push((key_length - 1, prev));
-        }
-
-        pprev = prev;
-        prev = fitness;
-        outer_key_len = key_length;
+///   The probable keys, and the char each key was found by
+///
+/// # Errors
+///   Returns any error from guessing the keys
+pub(crate) fn guess_keys_for_length(
+    ciphertext: &[u8],
+    window: &[u8],
+    try_chars: &[u8],
+    param: &Parameters,
+) -> Result<ProbableKeys, XorError> {
+    let (window_keys, window_key_char_used) =
+        guess_probable_keys_for_chars(window, try_chars, param)?;
+    let (mut probable_keys, key_char_used) =
+        align_keys(window_keys, window_key_char_used, param.skip_head);
+    if param.printable_key {
+        probable_keys = printable_keys(probable_keys);
     }
-
-    if pprev < prev {
-        fitnesses.push((outer_key_len - 1, prev));
+    if param.drop_null_key {
+        probable_keys = non_null_keys(probable_keys);
     }
-
-    fitnesses
+    if param.sort_by_validity {
+        probable_keys = sort_keys_by_validity(ciphertext, probable_keys, param);
+    }
+    Ok((probable_keys, key_char_used))
 }
 
-/// Pint out top 10 key lengths by fitness
+/// Print the per-column diagnostics that were asked for
 ///
-/// # Argument
-///   * `fitnesses`: Slice of tuples of the fitnesses. First element in tuple
-///     is key length. Second is fitness as a float.
-fn print_fitnesses(fitnesses: &[(i32, f64)]) {
-    let line_end = if env::consts::OS == "windows" {
-        "\r\n"
-    } else {
-        "\n"
-    };
-    print!("The most probable key lengths:{line_end}");
-
-    // Top sorted by fitness, but print sorted by length.
-    // NOTE: Original Python had sorting here, but we moved it to outer
-    // function. See the outer function for a comment on why
-
-    let mut top10: Vec<(i32, f64)> = fitnesses.iter().take(10).copied().collect();
-    let best_fitness = top10[0].1;
-    top10.sort_by_key(|v| v.0);
-
-    let fitness_sum = calc_fitness_sum(&top10);
-    // FIXME: Can we do this without string formatting?
-    let largest_number = top10.iter().map(|v| v.0).max().unwrap();
-    let largest_width = format!("{largest_number}").len();
-
-    for (key_length, fitness) in top10 {
-        let pct = 100.0 * fitness * 1.0 / fitness_sum;
-        #[expect(
-            clippy::float_cmp,
-            reason = "best_fitness is taken from within to10, so one is guaranteed to be an identical match."
-        )]
-        if fitness == best_fitness {
-            print!(
-                "{}{key_length:>width$}{}: {}{pct:5.1}%{}{line_end}",
-                *C_BEST_KEYLEN,
-                *C_RESET,
-                *C_BEST_PROB,
-                *C_RESET,
-                width = largest_width
-            );
-        } else {
-            print!(
-                "{}{key_length:>width$}{}: {}{pct:5.1}%{}{line_end}",
-                *C_KEYLEN,
-                *C_RESET,
-                *C_PROB,
-                *C_RESET,
-                width = largest_width
-            );
-        }
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `key_length`: The key length to split the data into columns with
+///   * `param`: Command line parameters supplied to the tool
+fn print_column_diagnostics(text: &[u8], key_length: i32, param: &Parameters) {
+    if param.column_entropy {
+        print_column_entropies(text, key_length);
+    }
+    if param.dump_columns && !param.quiet {
+        print_columns(text, key_length);
+    }
+    if param.show_ambiguity {
+        print_ambiguity(text, key_length);
+    }
+    if param.key_confidence && !param.quiet {
+        print_key_confidence(text, key_length);
     }
 }
 
-/// Compute the sum of all of the fitnesses
+/// Get the characters to try as the most frequent character of the plaintext
 ///
 /// # Arguments
-///   * `fitnesses`: The fitnesses to sum
+///   * `param`: Command line parameters supplied to the tool
 ///
 /// # Returns
-///   Sum of all of the fitnesses
-fn calc_fitness_sum(fitnesses: &[(i32, f64)]) -> f64 {
-    fitnesses.iter().map(|v| v.1).sum()
+///   The characters to try, or `None` if no way of choosing them was given
+fn most_frequent_chars(param: &Parameters) -> Option<Vec<u8>> {
+    if param.brute_chars {
+        Some((0..=255).collect())
+    } else if param.brute_printable {
+        Some(
+            PREDEFINED_CHARSETS
+                .get("printable")
+                .unwrap()
+                .bytes()
+                .collect(),
+        )
+    } else if let Some(most_frequent_char) = param.most_frequent_char {
+        Some(vec![most_frequent_char])
+    } else if param.auto_char {
+        // Spaces are the most common byte of most text, so the most common
+        // byte of each key offset is most likely a space xored with the key.
+        Some(vec![AUTO_CHAR])
+    } else {
+        None
+    }
 }
 
-/// Count number of equal characters at all offsets up to `key_length` and sum
+/// Print the shell completions or charset listing, if either was asked for
 ///
 /// # Arguments
-///   * `text`: The text to count the characters of
-///   * `key_length`: The length of the key used to encrypt the data
+///   * `param`: Command line parameters supplied to the tool
 ///
 /// # Returns
-///   Sum of the counts of most common character at each offset up to `key_length`
-fn count_equals(text: &[u8], key_length: i32) -> i32 {
-    let mut equals_count = 0;
-    if usize::try_from(key_length).unwrap() >= text.len() {
-        return 0;
+///   Whether a listing was printed, in which case there is nothing to analyze
+fn print_listings(param: &Parameters) -> bool {
+    if let Some(shell) = param.completions {
+        generate(
+            shell,
+            &mut Parameters::command(),
+            "xortool",
+            &mut io::stdout(),
+        );
+        true
+    } else if param.list_charsets {
+        message!("{}", charset_listing());
+        true
+    } else {
+        false
     }
+}
 
-    for offset in 0..key_length {
-        let chars_count = chars_count_at_offset(text, key_length, offset);
-        equals_count += chars_count.values().max().unwrap() - 1;
-    }
-    equals_count
+/// Describe the resolved parameters, for `--echo-params`
+///
+/// # Arguments
+///   * `key_lengths`: The key lengths being tried
+///   * `param`: Command line parameters supplied to the tool, after the
+///     defaults that depend on the input have been filled in
+///
+/// # Returns
+///   A heading, then the lines of `parameter_lines`
+fn echo_params(key_lengths: &[i32], param: &Parameters) -> String {
+    format!(
+        "Resolved parameters:{}{}",
+        line_end(),
+        parameter_lines(key_lengths, param)
+    )
 }
 
-/// Guess and print common divisions and return the most common divisor
+/// Describe the parameters that affect the analysis, in a readable form
 ///
 /// # Arguments
-///   * `fitnesses`: Slice of tuples of (key length, fitness)
+///   * `key_lengths`: The key lengths being tried
+///   * `param`: Command line parameters supplied to the tool
 ///
 /// # Returns
-///   The most common divisor.
-fn guess_and_print_divisors(fitnesses: &[(i32, f64)], param: &Parameters) -> i32 {
-    let line_end = if env::consts::OS == "windows" {
-        "\r\n"
+///   One indented `name: value` line per parameter
+fn parameter_lines(key_lengths: &[i32], param: &Parameters) -> String {
+    let line_end = line_end();
+    let inputs = param.filenames.join(", ");
+    let input_format = if param.input_is_hex {
+        "hex"
+    } else if param.hexdump {
+        "hexdump"
+    } else if param.escaped {
+        "escaped"
     } else {
-        "\n"
+        "raw"
     };
-    let max_key_len = param.max_key_length.unwrap_or_default();
-
-    let mut divisors_counts = Vec::from([0]).repeat(usize::try_from(max_key_len).unwrap() + 1);
-    for &(key_length, _) in fitnesses {
-        for number in 3..=key_length {
-            if key_length % number == 0 {
-                divisors_counts[usize::try_from(number).unwrap()] += 1;
-            }
+    let key_length = if let Some(count) = param.scan_lengths {
+        format!("scan the {count} best lengths")
+    } else {
+        let lengths = key_lengths
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>()
+            .join(", ");
+        if param.key_lengths.is_empty() && param.seed_key.is_none() {
+            format!("{lengths} (guessed)")
+        } else {
+            lengths
+        }
+    };
+    let most_frequent_chars = match most_frequent_chars(param) {
+        Some(chars) if param.brute_chars || param.brute_printable => {
+            format!("{} chars", chars.len())
         }
+        Some(chars) => format!("'{}'", chars.escape_ascii()),
+        None => String::from("none"),
+    };
+    let score = param.score.to_possible_value().unwrap();
+
+    let mut lines = String::new();
+    for (name, value) in [
+        ("input", format!("{inputs} ({input_format})")),
+        ("key length", key_length),
+        (
+            "max key length",
+            param.max_key_length.unwrap_or_default().to_string(),
+        ),
+        ("most frequent char", most_frequent_chars),
+        (
+            "text charset",
+            format!(
+                "'{}' ({} bytes)",
+                param.text_charset.escape_ascii(),
+                param.text_charset.len()
+            ),
+        ),
+        ("charset ignore case", param.charset_ignore_case.to_string()),
+        ("threshold", format!("{}%", param.threshold.unwrap_or(95))),
+        ("score", score.get_name().to_owned()),
+        (
+            "known plaintext",
+            param.known_plain.as_ref().map_or_else(
+                || String::from("none"),
+                |plain| format!("'{}'", plain.escape_ascii()),
+            ),
+        ),
+        (
+            "skip",
+            format!("{} head, {} tail bytes", param.skip_head, param.skip_tail),
+        ),
+    ] {
+        write!(lines, "  {name}: {value}{line_end}").unwrap();
     }
-    let max_divisors = divisors_counts.iter().max().unwrap();
+    lines
+}
 
-    let mut limit = 3;
-    let mut ret = 2;
-    for (number, divisors_count) in divisors_counts.iter().enumerate() {
-        if divisors_count == max_divisors {
-            print!(
-                "Key-length can be {}{}*n{}{line_end}",
-                *C_DIV, number, *C_RESET
-            );
-            ret = number;
-            limit -= 1;
-            if limit == 0 {
-                return ret.try_into().unwrap();
-            }
-        }
+/// Get the characters to try as the most frequent character, telling the user about them
+///
+/// Warns if no way of choosing them was given, and says which character is
+/// assumed with `auto_char`.
+///
+/// # Arguments
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The characters to try, or `None` if no way of choosing them was given
+fn announce_most_frequent_chars(param: &Parameters) -> Option<Vec<u8>> {
+    let line_end = line_end();
+    let Some(try_chars) = most_frequent_chars(param) else {
+        message!(
+            "{}Most possible char is needed to guess the key!{}{line_end}",
+            *C_WARN,
+            *C_RESET
+        );
+        return None;
+    };
+    if param.auto_char && !param.quiet {
+        message!(
+            "Assuming the most frequent char is {}{AUTO_CHAR:#04x}{}{line_end}",
+            *C_COUNT,
+            *C_RESET
+        );
     }
-    ret.try_into().unwrap()
+    Some(try_chars)
 }
 
-/// Get the key length that has the highest fitness
+/// Set up where the output is written
 ///
 /// # Arguments
-///   * `fitnesses`: Slice of tuples of (key length, fitness)
+///   * `param`: Command line parameters supplied to the tool
 ///
 /// # Returns
-///   The key length that has the highest fitness.
-fn get_max_fitnessed_key_length(fitnesses: &[(i32, f64)]) -> i32 {
-    let mut max_fitness = 0.0;
-    let mut max_fitnessed_key_length = 0;
-    for &(key_length, fitness) in fitnesses {
-        if fitness > max_fitness {
-            max_fitness = fitness;
-            max_fitnessed_key_length = key_length;
-        }
+///   The output to write to, and the directory the output files are in.
+///   For tar archives the directory is only used in the entry names.
+///
+/// # Errors
+///   Returns any error from creating the archive or output directories
+fn open_output(param: &Parameters) -> Result<(Output, String), XorError> {
+    #[cfg(feature = "tar")]
+    if let Some(tar) = &param.tar {
+        return Ok((Output::tar(tar)?, DIRNAME.to_owned()));
     }
-    max_fitnessed_key_length
+    let base_dir = if param.keep_output {
+        mkdir(DIRNAME)?;
+        let run_dir = fresh_run_dir()?;
+        mkdir(&run_dir)?;
+        run_dir
+    } else if param.append_csv {
+        mkdir(DIRNAME)?;
+        DIRNAME.to_owned()
+    } else {
+        cleanup()?;
+        mkdir(DIRNAME)?;
+        DIRNAME.to_owned()
+    };
+    Ok((Output::Directory, base_dir))
 }
 
-/// Count occurrences of characters starting at `offset` every `key_length`
+/// Read in the encrypted data
 ///
-/// Starting at the index `offset`, for ever `key_length` characters, the
-/// value of a character is read and recorded. The total counts of found
-/// characters are then returned.
+/// When built with the `mmap` feature, files are memory-mapped instead
+/// of being read into memory.
 ///
 /// # Arguments
-///   * `text`: Data to count characters of
-///   * `key_length`: The length of the key used to encrypt the data
-///   * `offset`: Offset to start point for counting characters
+///   * `param`: Command line parameters supplied to the tool
 ///
 /// # Returns
+///   The bytes of the encrypted data. When several files are given, their
+///   contents are concatenated in order.
 ///
-///  `HashMap` where the keys are characters found in the data set, and the
-///   values are the number of occurrences of the character.
-fn chars_count_at_offset(text: &[u8], key_length: i32, offset: i32) -> HashMap<u8, i32> {
-    let mut chars_count = HashMap::new();
-    for pos in
-        (usize::try_from(offset).unwrap()..text.len()).step_by(usize::try_from(key_length).unwrap())
-    {
-        let c = text[pos];
-
-        match chars_count.entry(c) {
-            Entry::Vacant(e) => e.insert(1),
-            Entry::Occupied(e) => {
-                let tmp_ref = e.into_mut();
-                *tmp_ref += 1;
-                tmp_ref
+/// # Errors
+///   Returns `XorError::ArgParser` if standard input is combined with named
+///   files, or any error that occurred while reading the files.
+fn get_ciphertext(param: &Parameters) -> Result<FileData, XorError> {
+    let ciphertext = match param.filenames.as_slice() {
+        [filename] => load_input(filename)?,
+        filenames => {
+            if filenames.iter().any(|filename| filename == "-") {
+                return Err(XorError::ArgParser {
+                    msg: String::from("Standard input (-) can't be combined with named files"),
+                });
             }
-        };
+            let mut combined = Vec::new();
+            for filename in filenames {
+                combined.extend(load_file(filename)?);
+            }
+            FileData::Owned(combined)
+        }
+    };
+    let ciphertext = ensure_not_empty(ciphertext, param)?;
+
+    #[cfg(feature = "gzip")]
+    let ciphertext = if param.gzip_input {
+        FileData::Owned(decompress(&ciphertext)?)
+    } else {
+        ciphertext
+    };
+
+    let ciphertext = if param.input_is_hex {
+        let decoded = decode_from_hex(&ciphertext)?;
+        if decoded.is_empty() {
+            return Err(XorError::UnicodeDecode {
+                msg: String::from("No hex digits found in the input"),
+            });
+        }
+        FileData::Owned(decoded)
+    } else if param.hexdump {
+        FileData::Owned(decode_from_hexdump(&ciphertext)?)
+    } else if param.escaped {
+        FileData::Owned(decode_from_escaped(&ciphertext)?)
+    } else {
+        ciphertext
+    };
+    select_input_range(ciphertext, param)
+}
+
+/// Check that some data was loaded
+///
+/// # Arguments
+///   * `ciphertext`: The loaded input
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The input, unchanged
+///
+/// # Errors
+///   Returns `XorError::IO` if the input is empty, naming standard input or
+///   the input files
+fn ensure_not_empty(ciphertext: FileData, param: &Parameters) -> Result<FileData, XorError> {
+    if !ciphertext.is_empty() {
+        return Ok(ciphertext);
+    }
+    let source = if param.filenames == ["-"] {
+        String::from("standard input")
+    } else {
+        param.filenames.join(", ")
+    };
+    Err(XorError::IO {
+        msg: format!("No data was read from {source}"),
+    })
+}
+
+/// Select the byte range of the input given by `input_offset` and `input_length`
+///
+/// # Arguments
+///   * `ciphertext`: The loaded input
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The bytes from `input_offset` on, at most `input_length` of them, or the
+///   input unchanged if neither option was given.
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if `input_offset` is at or past the end of
+///   the input, or `input_length` is zero, since that leaves nothing to analyze.
+fn select_input_range(ciphertext: FileData, param: &Parameters) -> Result<FileData, XorError> {
+    if param.input_offset.is_none() && param.input_length.is_none() {
+        return Ok(ciphertext);
+    }
+    if param.input_length == Some(0) {
+        return Err(XorError::ArgParser {
+            msg: String::from("--input-length must be at least 1"),
+        });
+    }
+    let offset = param.input_offset.unwrap_or(0);
+    if offset >= ciphertext.len() {
+        return Err(XorError::ArgParser {
+            msg: format!(
+                "--input-offset {offset} is at or past the end of the {} byte input",
+                ciphertext.len()
+            ),
+        });
+    }
+    let end = param
+        .input_length
+        .map_or(ciphertext.len(), |length| offset.saturating_add(length))
+        .min(ciphertext.len());
+    Ok(FileData::Owned(ciphertext[offset..end].to_vec()))
+}
+
+/// Get the part of the ciphertext to analyze
+///
+/// # Arguments
+///   * `ciphertext`: The full encrypted data
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The ciphertext with `skip_head` bytes removed from the front and
+///   `skip_tail` bytes removed from the back.
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if the skipped regions leave no data to analyze.
+fn analysis_window<'data>(
+    ciphertext: &'data [u8],
+    param: &Parameters,
+) -> Result<&'data [u8], XorError> {
+    let skipped = param.skip_head.saturating_add(param.skip_tail);
+    if skipped > 0 && skipped >= ciphertext.len() {
+        return Err(XorError::ArgParser {
+            msg: format!(
+                "--skip-head and --skip-tail skip {skipped} bytes, but the input is only {} bytes long",
+                ciphertext.len()
+            ),
+        });
+    }
+    Ok(&ciphertext[param.skip_head..ciphertext.len() - param.skip_tail])
+}
+
+/// Get the part of the data to guess the key length from
+///
+/// # Arguments
+///   * `text`: The data being analyzed
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The first `sample_bytes` bytes of `text`, or all of it if no sample
+///   size was given or `text` is shorter.
+fn key_length_sample<'data>(text: &'data [u8], param: &Parameters) -> &'data [u8] {
+    match param.sample_bytes {
+        Some(sample_bytes) => &text[..sample_bytes.min(text.len())],
+        None => text,
+    }
+}
+
+/// Get the maximum key length to probe when `-m` is not given
+///
+/// Key lengths above a quarter of the input leave fewer than four bytes per
+/// column, which is too few for a meaningful fitness. At least 2 lengths are
+/// probed, as a single probed length is reported as key length 0.
+///
+/// # Arguments
+///   * `text_len`: Length of the data being analyzed
+///
+/// # Returns
+///   A quarter of `text_len`, limited to between 2 and `DEFAULT_MAX_KEY_LENGTH`
+fn default_max_key_length(text_len: usize) -> i32 {
+    i32::try_from(text_len / 4)
+        .unwrap_or(i32::MAX)
+        .clamp(2, DEFAULT_MAX_KEY_LENGTH)
+}
+
+/// Get the maximum key length to probe
+///
+/// # Arguments
+///   * `text`: The data the key length is guessed from
+///   * `param`: Command line parameters provided to the tool
+///
+/// # Returns
+///   The maximum key length given in `param`, or the default for the length
+///   of `text` if none was given
+fn max_key_length(text: &[u8], param: &Parameters) -> i32 {
+    param
+        .max_key_length
+        .unwrap_or_else(|| default_max_key_length(text.len()))
+}
+
+/// Read a single input file (or stdin)
+///
+/// # Arguments
+///   * `filename`: The name of the file to load from, or `-` to load
+///     from standard input
+///
+/// # Returns
+///   The bytes of the file, memory-mapped if the `mmap` feature is enabled.
+fn load_input(filename: &str) -> Result<FileData, XorError> {
+    #[cfg(feature = "mmap")]
+    return load_file_mmap(filename);
+    #[cfg(not(feature = "mmap"))]
+    Ok(FileData::Owned(load_file(filename)?))
+}
+
+/// Get the most frequent bytes of some data
+///
+/// # Arguments
+///   * `text`: The data to count the bytes of
+///   * `top_n`: The maximum number of bytes to return
+///
+/// # Returns
+///   Vector of (byte, count) tuples, sorted by descending count. Bytes with
+///   equal counts are sorted in ascending order.
+fn byte_histogram(text: &[u8], top_n: usize) -> Vec<(u8, i32)> {
+    let mut counts: Vec<(u8, i32)> = chars_count_at_offset(text, 1, 0).into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts.truncate(top_n);
+    counts
+}
+
+/// Print the most frequent bytes of the data
+///
+/// # Arguments
+///   * `text`: The data to print the histogram of
+///   * `top_n`: The number of bytes to print
+fn print_histogram(text: &[u8], top_n: usize) {
+    let line_end = line_end();
+    message!("The most frequent bytes:{line_end}");
+    for (byte, count) in byte_histogram(text, top_n) {
+        #[expect(
+            clippy::cast_precision_loss,
+            clippy::as_conversions,
+            reason = "Need to convert to float for division."
+        )]
+        let pct = 100.0 * f64::from(count) / text.len() as f64;
+        message!(
+            "{}{byte:#04x}{} {:<6} {}{count}{} ({pct:5.1}%){line_end}",
+            *C_KEY,
+            *C_RESET,
+            format!("'{}'", to_printable_key(&[byte])),
+            *C_COUNT,
+            *C_RESET
+        );
     }
-    chars_count
 }
 
 // -----------------------------------------------------------------------------
-// KEYS GUESSING SECTION
+// KEYLENGTH GUESSING SECTION
 // -----------------------------------------------------------------------------
 
-/// Guess probably keys for all of a list of possible most common characters
+/// Guess the length of the key used to encrypt the data
 ///
 /// # Arguments
 ///   * `text`: The encrypted data
-///   * `try_chars`: Characters to try as the most common character.
+///   * `param`: Command line parameters provided to the tool
+///
+/// # Returns
+///   The guessed length of the key used to encrypt the data, and the ranked
+///   key length candidates it was picked from.
+///
+/// # Error
+///   Returns `XorError::Analysis` if no candidates could be found for key length.
+fn guess_key_length(text: &[u8], param: &Parameters) -> Result<(i32, Fitnesses), XorError> {
+    if param.entropy {
+        print_entropy(text, param);
+    }
+    let fitnesses = rank_key_lengths(text, param)?;
+    if let Some(path) = &param.fitness_out {
+        fs::write(path, fitness_table(&fitnesses))?;
+    }
+    if !param.quiet {
+        print_fitnesses(&KeyLengthReport::new(&fitnesses));
+    }
+    let key_length = pick_key_length(&fitnesses, param);
+    if !param.quiet && is_too_short_for_key_length(text.len(), key_length) {
+        message!(
+            "{}File is too short for reliable analysis: key length {key_length} leaves at most 2 bytes per key byte{}{}",
+            *C_WARN,
+            *C_RESET,
+            line_end()
+        );
+    }
+    Ok((key_length, fitnesses))
+}
+
+/// Warn if the key length would be guessed from (nearly) constant data
+///
+/// # Arguments
+///   * `sample`: The data the key length would be guessed from
 ///   * `param`: Command line parameters supplied to the tool
 ///
 /// # Returns
-///   * Vector of Vectors, where each inner vector is the bytes of a probable key
-///   * `HashMap` that maps the probable keys to the most common char they were found by.
-fn guess_probable_keys_for_chars(
-    text: &[u8],
-    try_chars: &[u8],
-    param: &Parameters,
-) -> (Vec<Vec<u8>>, HashMap<Vec<u8>, u8>) {
-    let mut probable_keys = Vec::new();
-    let mut key_char_used = HashMap::new();
-    for c in try_chars {
-        let keys = guess_keys(text, *c, param);
-        for key in keys {
-            key_char_used.insert(key.clone(), *c);
-            if !probable_keys.contains(&key) {
-                probable_keys.push(key);
-            }
-        }
+///   True if the warning was printed and the analysis should stop. Giving
+///   the key length with `-l` or `--seed-key` skips the check.
+fn warn_if_constant(sample: &[u8], param: &Parameters) -> bool {
+    let constant =
+        param.key_lengths.is_empty() && param.seed_key.is_none() && is_near_constant(sample);
+    if constant {
+        message!(
+            "{}The ciphertext is almost entirely one repeated byte, so it has no structure to guess the key length from. Use -l to analyze it anyway{}{}",
+            *C_WARN,
+            *C_RESET,
+            line_end()
+        );
     }
-    (probable_keys, key_char_used)
+    constant
 }
 
-/// Guess keys for the given text, based on the known most frequent character
+/// Check if the text is (nearly) all the same byte
 ///
-/// # Arguments:
+/// Such text looks equally good for every key length, so guessing the key
+/// length from it is meaningless.
+///
+/// # Arguments
 ///   * `text`: The encrypted data
-///   * `most_char`: The most common character in the decrypted data
-///   * `param`: Command line parameters supplied to the tool.
 ///
 /// # Returns
-///   Vector of vectors of bytes for possible keys
-fn guess_keys(text: &[u8], most_char: u8, param: &Parameters) -> Vec<Vec<u8>> {
-    let key_length = param.known_key_length.unwrap_or_default();
+///   True if at least 99% of the text is a single byte value
+fn is_near_constant(text: &[u8]) -> bool {
+    let mut counts: [usize; 256] = [0; 256];
+    for &byte in text {
+        counts[usize::from(byte)] += 1;
+    }
+    let max_count = counts.iter().max().copied().unwrap_or_default();
+    !text.is_empty() && max_count * 100 >= text.len() * 99
+}
 
-    let mut key_possible_bytes = Vec::new();
-    for _ in 0..key_length {
-        key_possible_bytes.push(Vec::new());
+/// Check if the text is too short to reliably guess a key of the given length
+///
+/// # Arguments
+///   * `text_len`: The length of the encrypted data
+///   * `key_length`: The guessed key length
+///
+/// # Returns
+///   True if the key length is at least half the text length
+fn is_too_short_for_key_length(text_len: usize, key_length: i32) -> bool {
+    usize::try_from(key_length).is_ok_and(|key_length| key_length >= text_len / 2)
+}
+
+/// Get the key lengths to try, guessing one if none were given
+///
+/// # Arguments
+///   * `sample`: The encrypted data to guess the key length from
+///   * `param`: Command line parameters provided to the tool
+///
+/// # Returns
+///   The key lengths to try, and the ranked key length candidates. The
+///   candidates are empty when the key length was given instead of guessed.
+///
+/// # Errors
+///   Returns any error from checking the seed key or guessing the key length
+pub(crate) fn select_key_lengths(
+    sample: &[u8],
+    param: &Parameters,
+) -> Result<(Vec<i32>, Fitnesses), XorError> {
+    if let Some(seed_key) = &param.seed_key {
+        Ok((seed_key_lengths(seed_key, &param.key_lengths)?, Vec::new()))
+    } else if param.key_lengths.is_empty() {
+        let (key_length, fitnesses) = guess_key_length(sample, param)?;
+        Ok((vec![key_length], fitnesses))
+    } else {
+        Ok((param.key_lengths.clone(), Vec::new()))
     }
+}
 
-    for offset in 0..key_length {
-        let chars_count = chars_count_at_offset(text, key_length, offset);
-        let max_count = *chars_count.values().max().unwrap();
-        for (character, count) in chars_count {
-            if count >= max_count {
-                key_possible_bytes[usize::try_from(offset).unwrap()].push(character ^ most_char);
-            }
-        }
+/// Get the key length given as a percentage of the input length
+///
+/// # Arguments
+///   * `text_len`: The length of the encrypted data
+///   * `pct`: The key length in percent of `text_len`
+///
+/// # Returns
+///   The key length, rounded to the nearest byte
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if the key length is not between 1 and
+///   `text_len`
+fn key_length_from_pct(text_len: usize, pct: f64) -> Result<i32, XorError> {
+    #[expect(
+        clippy::cast_precision_loss,
+        clippy::as_conversions,
+        reason = "Inputs are far too small for the precision loss to change the rounded length."
+    )]
+    let text_len_f64 = text_len as f64;
+    let key_length = (text_len_f64 * pct / 100.0).round();
+    if !(1.0..=text_len_f64).contains(&key_length) {
+        return Err(XorError::ArgParser {
+            msg: format!(
+                "--keylen-pct {pct} gives key length {key_length}, but it must be between 1 and {text_len}"
+            ),
+        });
     }
-    all_keys(&key_possible_bytes, &[], 0)
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::as_conversions,
+        reason = "Value is a whole number of at least 1, and saturates for inputs over 2 GiB."
+    )]
+    Ok(key_length as i32)
 }
 
-/// Product all combinations of possible key chars
+/// Get the key lengths to try when a seed key is given
 ///
 /// # Arguments
-///   * `key_possible_bytes`: Vector of vectors, where each sub-vector
-///     is a set of characers used in a key
-///   * `key_part`: Portion of a possible key
-///   * `offset`: Offset into `key_possible_bytes` to loop over.
+///   * `seed_key`: The partially known key
+///   * `key_lengths`: The key lengths given with `-l`, if any
 ///
-/// # Warning
-///   This function is recursive
+/// # Returns
+///   The length of the seed key
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if a key length given with `-l` does not
+///   match the length of the seed key
+fn seed_key_lengths(seed_key: &[Option<u8>], key_lengths: &[i32]) -> Result<Vec<i32>, XorError> {
+    let seed_length = i32::try_from(seed_key.len()).unwrap_or(i32::MAX);
+    if let Some(key_length) = key_lengths.iter().find(|&&length| length != seed_length) {
+        return Err(XorError::ArgParser {
+            msg: format!(
+                "--seed-key has {seed_length} bytes, but key length {key_length} was given"
+            ),
+        });
+    }
+    Ok(vec![seed_length])
+}
+
+/// Calculate the fitness of candidate key lengths, best first
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `param`: Command line parameters provided to the tool
 ///
 /// # Returns
-///   Vector of vectors of the possible key combinations
-fn all_keys(key_possible_bytes: &Vec<Vec<u8>>, key_part: &[u8], offset: usize) -> Vec<Vec<u8>> {
-    let mut keys = Vec::new();
-    if offset >= key_possible_bytes.len() {
-        return Vec::from([key_part.to_vec()]);
+///   Vector of (key length, fitness) tuples sorted by descending fitness
+///
+/// # Error
+///   Returns `XorError::Analysis` if no candidates could be found for key length.
+fn rank_key_lengths(text: &[u8], param: &Parameters) -> Result<Vec<(i32, f64)>, XorError> {
+    let max_key_len = max_key_length(text, param);
+    if max_key_len < 1 {
+        return Err(XorError::Analysis {
+            msg: format!("Maximum key length must be at least 1, got {max_key_len}"),
+        });
     }
-    for c in &key_possible_bytes[offset] {
-        let mut tmp = key_part.to_vec();
-        tmp.push(*c);
-        keys.extend(all_keys(key_possible_bytes, &tmp, offset + 1));
+    if let Some(step) = param.multiple_of
+        && step > max_key_len
+    {
+        return Err(XorError::ArgParser {
+            msg: format!(
+                "--multiple-of {step} is larger than the maximum key length {max_key_len}"
+            ),
+        });
     }
-    keys
+    let mut fitnesses = if param.autocorr {
+        autocorrelation_fitnesses(text, param)
+    } else {
+        calculate_fitnesses(text, param)
+    };
+    if fitnesses.is_empty() {
+        return Err(XorError::Analysis {
+            msg: String::from("No candidates for key length found! Too small file?"),
+        });
+    }
+    // Sorting here instead of inside KeyLengthReport::new since
+    // in Python, the list was passed by reference and thus sorted for all
+    // later functions. But here we pass a immutable slice, so if we sorted
+    // in the function, it would only apply to that function
+    fitnesses.sort_by(|a, b| a.1.total_cmp(&b.1));
+    fitnesses.reverse();
+    Ok(fitnesses)
 }
 
-/// Print out all of the keys that the tool has guessed
+/// Pick the key length to use from the ranked candidates
+///
+/// Also prints the common divisors unless `quiet` is set.
 ///
 /// # Arguments
-///   * `keys`: The keys that the tool has guessed.
-fn print_keys(keys: &[Vec<u8>]) {
-    let line_end = if env::consts::OS == "windows" {
-        "\r\n"
+///   * `fitnesses`: Slice of (key length, fitness) tuples, best first
+///   * `param`: Command line parameters provided to the tool
+///
+/// # Returns
+///   The key length to use
+fn pick_key_length(fitnesses: &[(i32, f64)], param: &Parameters) -> i32 {
+    let divisor = guess_and_print_divisors(fitnesses, param);
+    let best_key_length = if param.autocorr {
+        shortest_key_length_within(fitnesses, AUTOCORR_TOLERANCE)
     } else {
-        "\n"
+        match param.score {
+            Score::Weighted | Score::PrintableWeighted => get_max_fitnessed_key_length(fitnesses),
+            Score::Agreement => shortest_key_length_within(fitnesses, AGREEMENT_TOLERANCE),
+        }
     };
-    if keys.is_empty() {
-        print!("No keys guessed!{line_end}");
-        return;
+    if param.use_divisor
+        && let Some(divisor) = divisor
+    {
+        return prefer_divisor(fitnesses, best_key_length, divisor);
     }
-    print!(
-        "{}{}{} possible key(s) of length {}{}{}:{line_end}",
-        *C_COUNT,
-        keys.len(),
-        *C_RESET,
-        *C_COUNT,
-        keys[0].len(),
-        *C_RESET
-    );
+    best_key_length
+}
 
-    for key in keys.iter().take(5) {
-        print!("{}{}{}{line_end}", *C_KEY, to_printable_key(key), *C_RESET);
+/// Print the entropy of the ciphertext, and warn if it is suspiciously high
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `param`: Command line parameters provided to the tool
+fn print_entropy(text: &[u8], param: &Parameters) {
+    let line_end = line_end();
+    let text_entropy = entropy(text);
+    message!("Ciphertext entropy: {text_entropy:.3} bits/byte{line_end}");
+    if text_entropy > ENTROPY_WARN_THRESHOLD && !param.no_entropy_warning {
+        message!(
+            "{}Entropy is very high, input may be compressed or encrypted rather than simple XOR{}{line_end}",
+            *C_WARN,
+            *C_RESET
+        );
+    }
+}
+
+/// Print the entropy of each column of the ciphertext for a key length
+///
+/// At the correct key length each column is a simple substitution of the
+/// plaintext, so its entropy is as low as the plaintext's.
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `key_length`: The key length to split the data into columns with
+fn print_column_entropies(text: &[u8], key_length: i32) {
+    let line_end = line_end();
+    let entropies = column_entropies(text, key_length);
+    message!("Column entropy for key length {key_length}:{line_end}");
+    for (offset, column_entropy) in entropies.iter().enumerate() {
+        message!("  offset {offset:>3}: {column_entropy:.3} bits/byte{line_end}");
+    }
+    if !entropies.is_empty() {
+        #[expect(
+            clippy::cast_precision_loss,
+            clippy::as_conversions,
+            reason = "Need to convert to float for division."
+        )]
+        let mean = entropies.iter().sum::<f64>() / entropies.len() as f64;
+        message!("  mean      : {mean:.3} bits/byte{line_end}");
+    }
+}
+
+/// Print the bytes of each column of the ciphertext for a key length
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `key_length`: The key length to split the data into columns with
+fn print_columns(text: &[u8], key_length: i32) {
+    let line_end = line_end();
+    message!("Columns for key length {key_length}:{line_end}");
+    for offset in 0..key_length {
+        let column: Vec<String> = column_bytes(text, key_length, offset)
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        message!("  offset {offset:>3}: {}{line_end}", column.join(" "));
+    }
+}
+
+/// Get the bytes of the ciphertext encrypted with one key offset
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `key_length`: The length of the key used to encrypt the data
+///   * `offset`: The offset into the key
+///
+/// # Returns
+///   Iterator over every `key_length`th byte, starting at `offset`
+fn column_bytes(text: &[u8], key_length: i32, offset: i32) -> impl Iterator<Item = u8> {
+    text.iter()
+        .skip(usize::try_from(offset).unwrap())
+        .step_by(usize::try_from(key_length).unwrap())
+        .copied()
+}
+
+/// Compute the entropy of each column of the ciphertext for a key length
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `key_length`: The key length to split the data into columns with
+///
+/// # Returns
+///   The entropy of each column in bits per byte, indexed by key offset
+fn column_entropies(text: &[u8], key_length: i32) -> Vec<f64> {
+    (0..key_length)
+        .map(|offset| {
+            let counts = chars_count_at_offset(text, key_length, offset);
+            entropy_from_counts(
+                counts
+                    .into_values()
+                    .map(|count| usize::try_from(count).unwrap()),
+            )
+        })
+        .collect()
+}
+
+/// Boost the fitness of a key length if it is preferred with `--prefer-length`
+///
+/// The preferred length and its multiples have their fitness multiplied by
+/// the boost factor, so they win near-ties but not clear mismatches.
+///
+/// # Arguments
+///   * `key_length`: The key length the fitness is for
+///   * `fitness`: The fitness of the key length
+///   * `param`: The command line parameters passed to the tool
+///
+/// # Returns
+///   The fitness, boosted if the key length is preferred
+fn preferred_fitness(key_length: i32, fitness: f64, param: &Parameters) -> f64 {
+    match param.prefer_length {
+        Some(preferred) if key_length % preferred == 0 => {
+            fitness * param.prefer_boost.unwrap_or(DEFAULT_PREFER_BOOST)
+        }
+        Some(_) | None => fitness,
+    }
+}
+
+/// Calculate fitness of different key lengths
+///
+/// # Arguments
+///   * `text`: The encrypted data to decode
+///   * `param`: The command line parameters passed to the tool
+///
+/// # Returns
+///   Vector of tuples. For each tuple, the first element is a key
+///   length, and the second is the fitness of that key length
+fn calculate_fitnesses(text: &[u8], param: &Parameters) -> Vec<(i32, f64)> {
+    let mut prev = 0.0;
+    let mut pprev = 0.0;
+    let mut fitnesses = Vec::new();
+
+    let max_key_len = max_key_length(text, param);
+    let range_end = max_key_len.saturating_add(1);
+
+    // Distance between probed key lengths, so the previous length is `key_length - step`
+    let step = param.multiple_of.unwrap_or(1);
+
+    let mut outer_key_len = 0;
+
+    for key_length in (step..range_end).step_by(usize::try_from(step).unwrap_or(1)) {
+        let fitness = f64::from(match param.score {
+            Score::PrintableWeighted => count_printable_equals(text, key_length),
+            Score::Weighted | Score::Agreement => count_equals_transposed(text, key_length),
+        });
+
+        let fitness = match param.score {
+            Score::Weighted | Score::PrintableWeighted => {
+                fitness / (f64::from(max_key_len) + (f64::from(key_length)).powf(1.5))
+            }
+            Score::Agreement => fitness / comparisons(text, key_length),
+        };
+        let fitness = preferred_fitness(key_length, fitness, param);
+        #[cfg(feature = "logging")]
+        trace!("key length {key_length}: fitness {fitness}");
+
+        if pprev < prev && prev > fitness {
+            // Local maximum
+            #[cfg(feature = "logging")]
+            debug!(
+                "local maximum at key length {}: fitness {prev}",
+                key_length - step
+            );
+            fitnesses.push((key_length - step, prev));
+        }
+
+        pprev = prev;
+        prev = fitness;
+        outer_key_len = key_length;
+    }
+
+    if pprev < prev {
+        // Python xortool files the last fitness one below the length it was
+        // measured at. That is kept for matching output, but not when it would
+        // give a length that wasn't probed.
+        let last_key_len = if param.multiple_of.is_some() || outer_key_len <= step {
+            outer_key_len
+        } else {
+            outer_key_len - 1
+        };
+        fitnesses.push((last_key_len, prev));
+    }
+
+    fitnesses
+}
+
+/// Calculate the fitness of key lengths from the autocorrelation of the data
+///
+/// The fitness of a shift is the fraction of bytes equal to the byte that
+/// many positions later. Bytes encrypted with the same key byte are equal
+/// whenever their plaintext is, so the fraction peaks at multiples of the
+/// key length. Only the peaks are kept as candidates.
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `param`: The command line parameters passed to the tool
+///
+/// # Returns
+///   Vector of (shift, fitness) tuples for the shifts where the
+///   autocorrelation peaks
+fn autocorrelation_fitnesses(text: &[u8], param: &Parameters) -> Vec<(i32, f64)> {
+    let max_key_len = max_key_length(text, param);
+    let step = param.multiple_of.unwrap_or(1);
+    let rates: Vec<(i32, f64)> = (step..=max_key_len)
+        .step_by(usize::try_from(step).unwrap_or(1))
+        .map(|shift| (shift, autocorrelation(text, shift)))
+        .collect();
+
+    let mut fitnesses = Vec::new();
+    for (index, &(shift, rate)) in rates.iter().enumerate() {
+        let before = index
+            .checked_sub(1)
+            .and_then(|before| rates.get(before))
+            .map_or(0.0, |&(_, before)| before);
+        let after = rates.get(index + 1).map_or(0.0, |&(_, after)| after);
+        if before < rate && rate > after {
+            #[cfg(feature = "logging")]
+            debug!("autocorrelation peak at shift {shift}: {rate}");
+            fitnesses.push((shift, rate));
+        }
+    }
+    fitnesses
+}
+
+/// Calculate the autocorrelation of the data at a shift
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `shift`: The distance between the compared bytes
+///
+/// # Returns
+///   The fraction of bytes that are equal to the byte `shift` positions
+///   later, or 0 if the data is not longer than the shift
+fn autocorrelation(text: &[u8], shift: i32) -> f64 {
+    let shift = usize::try_from(shift).unwrap();
+    let Some(shifted) = text.get(shift..) else {
+        return 0.0;
+    };
+    if shifted.is_empty() {
+        return 0.0;
+    }
+    let matches = text.iter().zip(shifted).filter(|(a, b)| a == b).count();
+    #[expect(
+        clippy::cast_precision_loss,
+        clippy::as_conversions,
+        reason = "Need to convert to float for division."
+    )]
+    {
+        matches as f64 / shifted.len() as f64
+    }
+}
+
+/// Find the shortest key length whose fitness is close to the best
+///
+/// With `--score agreement` or `--autocorr` every multiple of the true key
+/// length scores about as well as the true length itself, so the highest
+/// fitness is often a multiple. Any length within `tolerance` of the best
+/// is considered equally good, and the shortest of those is picked.
+///
+/// # Arguments
+///   * `fitnesses`: Slice of tuples of (key length, fitness)
+///   * `tolerance`: Fraction of the best fitness a key length must reach
+///
+/// # Returns
+///   The shortest key length whose fitness is close to the best fitness
+fn shortest_key_length_within(fitnesses: &[(i32, f64)], tolerance: f64) -> i32 {
+    let best_fitness = fitnesses
+        .iter()
+        .map(|&(_, fitness)| fitness)
+        .fold(0.0, f64::max);
+    fitnesses
+        .iter()
+        .filter(|&&(_, fitness)| fitness >= best_fitness * tolerance)
+        .map(|&(key_length, _)| key_length)
+        .min()
+        .unwrap_or_default()
+}
+
+/// Number of byte comparisons `count_equals_transposed` makes for a key length
+///
+/// Each column of `key_length` bytes is compared against its first byte,
+/// so this is the total length minus one byte per column.
+///
+/// # Arguments
+///   * `text`: The encrypted data to decode
+///   * `key_length`: The key length to count comparisons for
+///
+/// # Returns
+///   The number of comparisons, or infinity if no comparisons are made so
+///   that dividing by it gives a fitness of zero
+fn comparisons(text: &[u8], key_length: i32) -> f64 {
+    match text.len().checked_sub(usize::try_from(key_length).unwrap()) {
+        Some(count) if count > 0 => {
+            #[expect(
+                clippy::cast_precision_loss,
+                clippy::as_conversions,
+                reason = "Need to convert to float for division."
+            )]
+            {
+                count as f64
+            }
+        }
+        _ => f64::INFINITY,
+    }
+}
+
+/// The top 10 key length candidates, with their share of the total fitness
+#[derive(Debug, PartialEq)]
+struct KeyLengthReport {
+    /// (key length, fitness, percentage) tuples, sorted by key length
+    candidates: Vec<(i32, f64, f64)>,
+    /// The key length with the highest fitness
+    best: i32,
+}
+
+impl KeyLengthReport {
+    /// Select the top 10 key lengths and compute their percentages
+    ///
+    /// # Arguments
+    ///   * `fitnesses`: Slice of (key length, fitness) tuples, best first.
+    ///     Must not be empty.
+    ///
+    /// # Returns
+    ///   The report of the top 10 candidates
+    fn new(fitnesses: &[(i32, f64)]) -> Self {
+        // Top sorted by fitness, but reported sorted by length.
+        // NOTE: Original Python had sorting here, but we moved it to outer
+        // function. See `rank_key_lengths` for a comment on why
+        let mut top10: Vec<(i32, f64)> = fitnesses.iter().take(10).copied().collect();
+        let best = top10[0].0;
+        top10.sort_by_key(|v| v.0);
+
+        let fitness_sum = calc_fitness_sum(&top10);
+        let candidates = top10
+            .into_iter()
+            .map(|(key_length, fitness)| (key_length, fitness, 100.0 * fitness / fitness_sum))
+            .collect();
+        Self { candidates, best }
+    }
+
+    /// Get the fitness of the best key length
+    ///
+    /// # Returns
+    ///   The fitness of `best`
+    fn best_fitness(&self) -> f64 {
+        self.candidates
+            .iter()
+            .find(|v| v.0 == self.best)
+            .map_or(0.0, |v| v.1)
+    }
+}
+
+/// Pint out top 10 key lengths by fitness
+///
+/// # Argument
+///   * `report`: The top 10 key lengths and their percentages
+fn print_fitnesses(report: &KeyLengthReport) {
+    let line_end = line_end();
+    message!("The most probable key lengths:{line_end}");
+
+    let best_fitness = report.best_fitness();
+    // FIXME: Can we do this without string formatting?
+    let largest_number = report.candidates.iter().map(|v| v.0).max().unwrap();
+    let largest_width = format!("{largest_number}").len();
+
+    for &(key_length, fitness, pct) in &report.candidates {
+        #[expect(
+            clippy::float_cmp,
+            reason = "best_fitness is taken from within the candidates, so one is guaranteed to be an identical match."
+        )]
+        if fitness == best_fitness {
+            message!(
+                "{}{key_length:>width$}{}: {}{pct:5.1}%{}{line_end}",
+                *C_BEST_KEYLEN,
+                *C_RESET,
+                *C_BEST_PROB,
+                *C_RESET,
+                width = largest_width
+            );
+        } else {
+            message!(
+                "{}{key_length:>width$}{}: {}{pct:5.1}%{}{line_end}",
+                *C_KEYLEN,
+                *C_RESET,
+                *C_PROB,
+                *C_RESET,
+                width = largest_width
+            );
+        }
+    }
+}
+
+/// Find the best plaintext reachable with each of the top key lengths
+///
+/// # Arguments
+///   * `ciphertext`: The encrypted data
+///   * `window`: The part of the encrypted data being analyzed
+///   * `fitnesses`: Slice of (key length, fitness) tuples, best first
+///   * `count`: Number of key lengths to scan
+///   * `try_chars`: Characters to try as the most common character
+///   * `param`: Command line parameters supplied to the tool. The key length
+///     being scanned is stored in `known_key_length`.
+///
+/// # Returns
+///   Vector of (key length, best fraction of valid characters) tuples, in the
+///   order of `fitnesses`
+///
+/// # Errors
+///   Returns any error from guessing the keys
+fn scan_key_lengths(
+    ciphertext: &[u8],
+    window: &[u8],
+    fitnesses: &[(i32, f64)],
+    count: usize,
+    try_chars: &[u8],
+    param: &mut Parameters,
+) -> Result<Vec<(i32, f64)>, XorError> {
+    let mut scan = Vec::new();
+    for &(key_length, _) in fitnesses.iter().take(count) {
+        param.known_key_length = Some(key_length);
+        let (probable_keys, _) = guess_keys_for_length(ciphertext, window, try_chars, param)?;
+        let best_valid = probable_keys
+            .iter()
+            .map(|key| percentage_valid(&dexor(ciphertext, key), param))
+            .fold(0.0, f64::max);
+        scan.push((key_length, best_valid));
+    }
+    Ok(scan)
+}
+
+/// Format the best plaintext reached with each scanned key length
+///
+/// # Arguments
+///   * `scan`: Slice of (key length, best fraction of valid characters) tuples
+///
+/// # Returns
+///   One line per key length, with the best percentage of valid characters
+fn length_scan_table(scan: &[(i32, f64)]) -> String {
+    let line_end = line_end();
+    let mut table = format!("Best plaintext per key length:{line_end}");
+    let width = scan
+        .iter()
+        .map(|v| v.0.to_string().len())
+        .max()
+        .unwrap_or(0);
+    for (key_length, best_valid) in scan {
+        write!(
+            table,
+            "{}{key_length:>width$}{}: {}{:5.1}%{}{line_end}",
+            *C_KEYLEN,
+            *C_RESET,
+            *C_PROB,
+            100.0 * best_valid,
+            *C_RESET,
+        )
+        .unwrap();
+    }
+    table
+}
+
+/// Format all candidate key lengths as a tab separated table
+///
+/// The probability is normalized the same way `KeyLengthReport` does,
+/// but over all candidates instead of just the top 10.
+///
+/// # Arguments
+///   * `fitnesses`: Slice of (key length, fitness) tuples, best first
+///
+/// # Returns
+///   The table with a header row and one row per candidate, best first
+fn fitness_table(fitnesses: &[(i32, f64)]) -> String {
+    let line_end = line_end();
+    let fitness_sum = calc_fitness_sum(fitnesses);
+    let mut table = format!("key_length\tfitness\tprobability{line_end}");
+    for (key_length, fitness) in fitnesses {
+        let pct = 100.0 * fitness / fitness_sum;
+        write!(table, "{key_length}\t{fitness}\t{pct:.1}{line_end}").unwrap();
+    }
+    table
+}
+
+/// Compute the sum of all of the fitnesses
+///
+/// # Arguments
+///   * `fitnesses`: The fitnesses to sum
+///
+/// # Returns
+///   Sum of all of the fitnesses
+fn calc_fitness_sum(fitnesses: &[(i32, f64)]) -> f64 {
+    fitnesses.iter().map(|v| v.1).sum()
+}
+
+/// Count number of equal characters at all offsets up to `key_length` and sum
+///
+/// The data is split into one contiguous column per key offset in a single
+/// pass, then each column is histogrammed with a fixed size array. This
+/// avoids re-walking the data with a stride for every offset, which is much
+/// faster on large inputs.
+///
+/// # Arguments
+///   * `text`: The text to count the characters of
+///   * `key_length`: The length of the key used to encrypt the data
+///
+/// # Returns
+///   Sum of the counts of most common character at each offset up to `key_length`
+fn count_equals_transposed(text: &[u8], key_length: i32) -> i32 {
+    let key_length = usize::try_from(key_length).unwrap();
+    if key_length >= text.len() {
+        return 0;
+    }
+
+    let mut equals_count = 0;
+    for column in &transpose(text, key_length) {
+        let mut counts: [usize; 256] = [0; 256];
+        for &byte in column {
+            counts[usize::from(byte)] += 1;
+        }
+        equals_count += counts.iter().max().unwrap() - 1;
+    }
+    #[cfg(feature = "logging")]
+    trace!("key length {key_length}: {equals_count} equal characters");
+    i32::try_from(equals_count).unwrap_or(i32::MAX)
+}
+
+/// Count equal characters like `count_equals_transposed`, but only in columns that
+/// look like text encrypted with a printable key
+///
+/// A column only counts if one of its most common bytes, assumed to be a
+/// space in the plaintext, gives a printable key byte.
+///
+/// # Arguments
+///   * `text`: The text to count the characters of
+///   * `key_length`: The length of the key used to encrypt the data
+///
+/// # Returns
+///   Sum of the counts of most common character at each counted offset
+fn count_printable_equals(text: &[u8], key_length: i32) -> i32 {
+    let key_length = usize::try_from(key_length).unwrap();
+    if key_length >= text.len() {
+        return 0;
+    }
+    let printable = PREDEFINED_CHARSETS["printable"].as_bytes();
+
+    let mut equals_count = 0;
+    for column in &transpose(text, key_length) {
+        let mut counts: [usize; 256] = [0; 256];
+        for &byte in column {
+            counts[usize::from(byte)] += 1;
+        }
+        let max_count = counts.iter().max().copied().unwrap_or_default();
+        let printable_key = (0..=255)
+            .filter(|&byte| counts[usize::from(byte)] == max_count)
+            .any(|byte: u8| printable.contains(&(byte ^ b' ')));
+        if printable_key {
+            equals_count += max_count - 1;
+        }
+    }
+    i32::try_from(equals_count).unwrap_or(i32::MAX)
+}
+
+/// Split data into one contiguous column per key offset
+///
+/// # Arguments
+///   * `text`: The data to split
+///   * `key_length`: The number of columns
+///
+/// # Returns
+///   The bytes at each key offset, indexed by offset
+fn transpose(text: &[u8], key_length: usize) -> Vec<Vec<u8>> {
+    let mut columns = vec![Vec::with_capacity(text.len() / key_length + 1); key_length];
+    for row in text.chunks(key_length) {
+        for (column, &byte) in columns.iter_mut().zip(row) {
+            column.push(byte);
+        }
+    }
+    columns
+}
+
+/// Guess and print common divisions and return the most common divisor
+///
+/// Only divisors of 3 or more are considered, so if every candidate key
+/// length is 1 or 2 there is no meaningful divisor and nothing is printed.
+/// When several divisors are shared by the same number of candidates, the
+/// largest one is returned, since the divisors of the true key length are
+/// shared by all of its multiples too.
+///
+/// # Arguments
+///   * `fitnesses`: Slice of tuples of (key length, fitness)
+///   * `param`: Command line parameters provided to the tool. Nothing is
+///     printed when `quiet` is set.
+///
+/// # Returns
+///   The largest of the most common divisors, or `None` if no candidate has a
+///   divisor.
+fn guess_and_print_divisors(fitnesses: &[(i32, f64)], param: &Parameters) -> Option<i32> {
+    let line_end = line_end();
+    let divisors = common_divisors(fitnesses);
+    if !param.quiet {
+        for divisor in shown_divisors(&divisors, param) {
+            message!(
+                "Key-length can be {}{}*n{}{line_end}",
+                *C_DIV,
+                divisor,
+                *C_RESET
+            );
+        }
+    }
+    divisors.last().copied()
+}
+
+/// Find the most common divisors of the candidate key lengths
+///
+/// # Arguments
+///   * `fitnesses`: Slice of tuples of (key length, fitness)
+///
+/// # Returns
+///   All divisors (of 3 or more) shared by the most candidates, smallest
+///   first. Empty if no candidate has a divisor.
+fn common_divisors(fitnesses: &[(i32, f64)]) -> Vec<i32> {
+    let max_key_len = fitnesses
+        .iter()
+        .map(|&(key_length, _)| key_length)
+        .max()
+        .unwrap_or_default()
+        .max(0);
+
+    let mut divisors_counts = Vec::from([0]).repeat(usize::try_from(max_key_len).unwrap() + 1);
+    for &(key_length, _) in fitnesses {
+        for number in 3..=key_length {
+            if key_length % number == 0 {
+                divisors_counts[usize::try_from(number).unwrap()] += 1;
+            }
+        }
+    }
+    let max_divisors = divisors_counts.iter().max().unwrap();
+    if *max_divisors == 0 {
+        return Vec::new();
+    }
+
+    divisors_counts
+        .iter()
+        .enumerate()
+        .filter(|&(_, divisors_count)| divisors_count == max_divisors)
+        .map(|(number, _)| i32::try_from(number).unwrap())
+        .collect()
+}
+
+/// Get the most common divisors to print
+///
+/// # Arguments
+///   * `divisors`: The most common divisors, smallest first
+///   * `param`: Command line parameters provided to the tool
+///
+/// # Returns
+///   The smallest 3 divisors like the Python xortool prints, or all of them
+///   if `all_divisors` is set.
+fn shown_divisors<'divisors>(divisors: &'divisors [i32], param: &Parameters) -> &'divisors [i32] {
+    if param.all_divisors {
+        divisors
+    } else {
+        &divisors[..divisors.len().min(MAX_SHOWN_DIVISORS)]
+    }
+}
+
+/// Get the key length that has the highest fitness
+///
+/// # Arguments
+///   * `fitnesses`: Slice of tuples of (key length, fitness)
+///
+/// # Returns
+///   The key length that has the highest fitness.
+fn get_max_fitnessed_key_length(fitnesses: &[(i32, f64)]) -> i32 {
+    let mut max_fitness = 0.0;
+    let mut max_fitnessed_key_length = 0;
+    for &(key_length, fitness) in fitnesses {
+        if fitness > max_fitness {
+            max_fitness = fitness;
+            max_fitnessed_key_length = key_length;
+        }
+    }
+    max_fitnessed_key_length
+}
+
+/// Choose between the most common divisor and the max-fitness key length
+///
+/// The support for a key length is the sum of the fitnesses of all
+/// candidates that are a multiple of it. The divisor is only chosen when
+/// its support is strictly greater than that of the max-fitness key length,
+/// so ties keep the max-fitness key length.
+///
+/// # Arguments
+///   * `fitnesses`: Slice of tuples of (key length, fitness)
+///   * `best_key_length`: The key length with the highest fitness
+///   * `divisor`: The most common divisor of the candidate key lengths
+///
+/// # Returns
+///   The key length to use.
+fn prefer_divisor(fitnesses: &[(i32, f64)], best_key_length: i32, divisor: i32) -> i32 {
+    let support = |length: i32| -> f64 {
+        fitnesses
+            .iter()
+            .filter(|v| v.0 % length == 0)
+            .map(|v| v.1)
+            .sum()
+    };
+    if support(divisor) > support(best_key_length) {
+        divisor
+    } else {
+        best_key_length
+    }
+}
+
+/// Count occurrences of characters starting at `offset` every `key_length`
+///
+/// Starting at the index `offset`, for ever `key_length` characters, the
+/// value of a character is read and recorded. The total counts of found
+/// characters are then returned.
+///
+/// # Arguments
+///   * `text`: Data to count characters of
+///   * `key_length`: The length of the key used to encrypt the data
+///   * `offset`: Offset to start point for counting characters
+///
+/// # Returns
+///
+///  `HashMap` where the keys are characters found in the data set, and the
+///   values are the number of occurrences of the character.
+fn chars_count_at_offset(text: &[u8], key_length: i32, offset: i32) -> HashMap<u8, i32> {
+    let mut chars_count = HashMap::new();
+    for c in column_bytes(text, key_length, offset) {
+        match chars_count.entry(c) {
+            Entry::Vacant(e) => e.insert(1),
+            Entry::Occupied(e) => {
+                let tmp_ref = e.into_mut();
+                *tmp_ref += 1;
+                tmp_ref
+            }
+        };
+    }
+    chars_count
+}
+
+// -----------------------------------------------------------------------------
+// KEYS GUESSING SECTION
+// -----------------------------------------------------------------------------
+
+/// Guess probably keys for all of a list of possible most common characters
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `try_chars`: Characters to try as the most common character.
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   * Vector of Vectors, where each inner vector is the bytes of a probable key,
+///     in the order they were first found
+///   * `HashMap` that maps the probable keys to the most common char they were
+///     found by. Keys found by several chars map to the last of those chars.
+///
+/// # Errors
+///   Returns the first error from guessing keys for a char, unless
+///   `keep_going` is set, in which case those chars are skipped.
+fn guess_probable_keys_for_chars(
+    text: &[u8],
+    try_chars: &[u8],
+    param: &Parameters,
+) -> Result<ProbableKeys, XorError> {
+    guess_probable_keys_with(text, try_chars, param, guess_keys)
+}
+
+/// Guess probable keys for each char with the given key guessing function
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `try_chars`: Characters to try as the most common character.
+///   * `param`: Command line parameters supplied to the tool
+///   * `guess`: Function guessing the keys for a single char
+///
+/// # Returns
+///   The probable keys, and the char each key was found by
+///
+/// Once `max_total_candidate_keys` keys are found, the remaining chars are
+/// not tried.
+///
+/// # Errors
+///   Returns the first error from `guess`, unless `keep_going` is set.
+fn guess_probable_keys_with(
+    text: &[u8],
+    try_chars: &[u8],
+    param: &Parameters,
+    guess: impl Fn(&[u8], u8, &Parameters) -> Result<Vec<Vec<u8>>, XorError>,
+) -> Result<ProbableKeys, XorError> {
+    let mut probable_keys = Vec::new();
+    let mut key_char_used = HashMap::new();
+    let mut skipped = Vec::new();
+    let max_total = param.max_total_candidate_keys.unwrap_or(usize::MAX);
+    for (tried, c) in try_chars.iter().enumerate() {
+        check_deadline(param, || {
+            format!(
+                "Tried {tried} of {} chars, and found {} keys",
+                try_chars.len(),
+                probable_keys.len()
+            )
+        })?;
+        let keys = match guess(text, *c, param) {
+            Ok(keys) => keys,
+            Err(e) if param.keep_going => {
+                skipped.push((*c, e));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let mut capped = false;
+        for key in keys {
+            if probable_keys.len() >= max_total && !key_char_used.contains_key(&key) {
+                capped = true;
+                break;
+            }
+            // The map doubles as the set of keys already found
+            if key_char_used.insert(key.clone(), *c).is_none() {
+                probable_keys.push(key);
+            }
+        }
+        if capped {
+            if !param.quiet {
+                message!(
+                    "{}Found {max_total} keys after trying {} of {} chars, skipping the rest{}{}",
+                    *C_WARN,
+                    tried + 1,
+                    try_chars.len(),
+                    *C_RESET,
+                    line_end()
+                );
+            }
+            break;
+        }
+    }
+    if !skipped.is_empty() && !param.quiet {
+        let line_end = line_end();
+        for (c, e) in &skipped {
+            message!(
+                "{}Skipped char {c:#04x}: {e}{}{line_end}",
+                *C_WARN,
+                *C_RESET
+            );
+        }
+        message!(
+            "{}Skipped {} of {} chars{}{line_end}",
+            *C_WARN,
+            skipped.len(),
+            try_chars.len(),
+            *C_RESET
+        );
+    }
+    Ok((probable_keys, key_char_used))
+}
+
+/// Guess keys for the given text, based on the known most frequent character
+///
+/// # Arguments:
+///   * `text`: The encrypted data
+///   * `most_char`: The most common character in the decrypted data
+///   * `param`: Command line parameters supplied to the tool.
+///
+/// # Returns
+///   Vector of vectors of bytes for possible keys
+///
+/// # Errors
+///   Returns `XorError::Analysis` if there is no data at an offset of the
+///   key to guess its byte from.
+fn guess_keys(text: &[u8], most_char: u8, param: &Parameters) -> Result<Vec<Vec<u8>>, XorError> {
+    let key_length = param.known_key_length.unwrap_or_default();
+
+    let mut key_possible_bytes = Vec::new();
+    for _ in 0..key_length {
+        key_possible_bytes.push(Vec::new());
+    }
+
+    for offset in 0..key_length {
+        let seed_byte = param
+            .seed_key
+            .as_ref()
+            .and_then(|seed_key| seed_key.get(usize::try_from(offset).unwrap()))
+            .copied()
+            .flatten();
+        let mut column_key_bytes: Vec<u8> = if let Some(seed_byte) = seed_byte {
+            vec![seed_byte]
+        } else {
+            match param.wide {
+                Some(endian) => {
+                    wide_key_bytes(text, key_length, offset, most_char, endian, param.skip_head)
+                }
+                None => most_common_bytes(text, key_length, offset)
+                    .into_iter()
+                    .map(|character| character ^ most_char)
+                    .collect(),
+            }
+        };
+        if column_key_bytes.is_empty() {
+            return Err(XorError::Analysis {
+                msg: format!(
+                    "No data to guess key offset {offset} from: key length {key_length} is longer than the {} bytes analyzed",
+                    text.len()
+                ),
+            });
+        }
+        if param.guess_only_printable_keys {
+            column_key_bytes.retain(|&byte| is_printable_key_byte(byte));
+            if column_key_bytes.is_empty() {
+                // No printable key byte at this offset, so no key is worth expanding
+                return Ok(Vec::new());
+            }
+        }
+        key_possible_bytes[usize::try_from(offset).unwrap()].extend(column_key_bytes);
+        #[cfg(feature = "logging")]
+        debug!(
+            "char {most_char:#04x}, offset {offset}: key bytes {:02x?}",
+            key_possible_bytes[usize::try_from(offset).unwrap()]
+        );
+    }
+    let max_keys = if param.unlimited_candidate_keys {
+        usize::MAX
+    } else {
+        param.max_candidate_keys
+    };
+    let combinations = count_key_combinations(&key_possible_bytes);
+    if combinations > max_keys && !param.quiet {
+        let line_end = line_end();
+        message!(
+            "{}Char {most_char:#04x} gives {combinations} possible keys, only keeping the first {max_keys}{}{line_end}",
+            *C_WARN,
+            *C_RESET
+        );
+    }
+    Ok(all_keys(&key_possible_bytes, &[], 0, max_keys))
+}
+
+/// Find the bytes that are tied for most common at an offset
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `key_length`: The length of the key
+///   * `offset`: The offset into the key
+///
+/// # Returns
+///   All bytes that occur the maximum number of times at the offset, in the
+///   order they first occur
+fn most_common_bytes(text: &[u8], key_length: i32, offset: i32) -> Vec<u8> {
+    tied_most_common(&column_bytes(text, key_length, offset).collect::<Vec<u8>>())
+}
+
+/// Find the bytes that are tied for the highest count
+///
+/// The bytes are ordered by first occurrence, like the Python xortool's
+/// dictionaries, so the guessed keys come out in the same order every run.
+///
+/// # Arguments
+///   * `column`: The bytes to count
+///
+/// # Returns
+///   All bytes with the maximum count, in the order they first occur
+fn tied_most_common(column: &[u8]) -> Vec<u8> {
+    let mut counts: [usize; 256] = [0; 256];
+    for &byte in column {
+        counts[usize::from(byte)] += 1;
+    }
+    let max_count = counts.iter().max().copied().unwrap_or_default();
+    let mut tied = Vec::new();
+    for &byte in column {
+        if counts[usize::from(byte)] == max_count && !tied.contains(&byte) {
+            tied.push(byte);
+        }
+    }
+    tied
+}
+
+/// Guess the key bytes at an offset for text of 16-bit characters
+///
+/// The zero high bytes of the characters give the key byte directly, so
+/// they are used when the column has any. Otherwise the low bytes are
+/// assumed to be `most_char` as usual.
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `key_length`: The length of the key
+///   * `offset`: The offset into the key
+///   * `most_char`: The most common character in the decrypted data
+///   * `endian`: The byte order of the characters
+///   * `start`: Position of `text` in the input, for the byte order
+///
+/// # Returns
+///   All key bytes tied for most likely at the offset
+fn wide_key_bytes(
+    text: &[u8],
+    key_length: i32,
+    offset: i32,
+    most_char: u8,
+    endian: Endian,
+    start: usize,
+) -> Vec<u8> {
+    let positions =
+        (usize::try_from(offset).unwrap()..).step_by(usize::try_from(key_length).unwrap());
+    let (high_bytes, low_bytes): (Vec<_>, Vec<_>) = positions
+        .zip(column_bytes(text, key_length, offset))
+        .partition(|&(position, _)| is_high_byte(start + position, endian));
+    if high_bytes.is_empty() {
+        let low_bytes: Vec<u8> = low_bytes.into_iter().map(|(_, byte)| byte).collect();
+        tied_most_common(&low_bytes)
+            .into_iter()
+            .map(|character| character ^ most_char)
+            .collect()
+    } else {
+        let high_bytes: Vec<u8> = high_bytes.into_iter().map(|(_, byte)| byte).collect();
+        tied_most_common(&high_bytes)
+    }
+}
+
+/// Check if a position holds the high byte of a 16-bit character
+///
+/// # Arguments
+///   * `position`: Position of the byte in the input
+///   * `endian`: The byte order of the characters
+///
+/// # Returns
+///   True if the byte is the high byte of its character
+fn is_high_byte(position: usize, endian: Endian) -> bool {
+    match endian {
+        Endian::Le => !position.is_multiple_of(2),
+        Endian::Be => position.is_multiple_of(2),
+    }
+}
+
+/// Print how many bytes are tied for most common at each key offset
+///
+/// Every tie multiplies the number of guessed keys, so offsets with more
+/// than one candidate are the uncertain parts of the key. The number of
+/// ties does not depend on the most frequent character being tried.
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `key_length`: The length of the key
+fn print_ambiguity(text: &[u8], key_length: i32) {
+    let line_end = line_end();
+    message!("Candidate bytes per offset for key length {key_length}:{line_end}");
+    for (offset, ties) in offset_ambiguity(text, key_length).iter().enumerate() {
+        if *ties > 1 {
+            message!(
+                "  offset {offset:>3}: {}{ties} candidates{}{line_end}",
+                *C_WARN,
+                *C_RESET
+            );
+        } else {
+            message!("  offset {offset:>3}: {ties} candidate{line_end}");
+        }
+    }
+}
+
+/// Count how many bytes are tied for most common at each key offset
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `key_length`: The length of the key
+///
+/// # Returns
+///   The number of tied bytes, indexed by key offset
+fn offset_ambiguity(text: &[u8], key_length: i32) -> Vec<usize> {
+    (0..key_length)
+        .map(|offset| most_common_bytes(text, key_length, offset).len())
+        .collect()
+}
+
+/// Print how strongly the key byte at each offset was determined
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `key_length`: The length of the key
+fn print_key_confidence(text: &[u8], key_length: i32) {
+    let line_end = line_end();
+    message!("Key byte confidence for key length {key_length}:{line_end}");
+    for (offset, confidence) in key_confidence(text, key_length).iter().enumerate() {
+        message!(
+            "  offset {offset:>3}: {}{:.1}%{}{line_end}",
+            *C_COUNT,
+            confidence * 100.0,
+            *C_RESET
+        );
+    }
+}
+
+/// Find how strongly the key byte at each offset is determined
+///
+/// The guessed key byte comes from the most common byte at its offset, so
+/// a most common byte that barely stands out from the rest of the column
+/// gives a key byte that is little better than a guess.
+///
+/// # Arguments
+///   * `text`: The encrypted data
+///   * `key_length`: The length of the key
+///
+/// # Returns
+///   The count of the most common byte as a fraction (0 to 1) of the bytes
+///   at the offset, indexed by key offset
+pub(crate) fn key_confidence(text: &[u8], key_length: i32) -> Vec<f64> {
+    (0..key_length)
+        .map(|offset| {
+            let chars_count = chars_count_at_offset(text, key_length, offset);
+            let total: i32 = chars_count.values().sum();
+            let max_count = chars_count.values().max().copied().unwrap_or_default();
+            if total == 0 {
+                0.0
+            } else {
+                f64::from(max_count) / f64::from(total)
+            }
+        })
+        .collect()
+}
+
+/// Rotate keys guessed on the analysis window so they apply to the full ciphertext
+///
+/// # Arguments
+///   * `keys`: Keys guessed from the analysis window
+///   * `key_char_used`: Map of the guessed keys to the character used to find them
+///   * `skip_head`: Number of bytes skipped at the start of the ciphertext
+///
+/// # Returns
+///   The rotated keys, and the map updated to use the rotated keys.
+fn align_keys(
+    mut keys: Vec<Vec<u8>>,
+    key_char_used: HashMap<Vec<u8>, u8>,
+    skip_head: usize,
+) -> (Vec<Vec<u8>>, HashMap<Vec<u8>, u8>) {
+    if skip_head == 0 {
+        return (keys, key_char_used);
+    }
+    let align = |key: &mut Vec<u8>| {
+        let key_length = key.len();
+        if key_length > 0 {
+            key.rotate_right(skip_head % key_length);
+        }
+    };
+    keys.iter_mut().for_each(align);
+    let key_char_used = key_char_used
+        .into_iter()
+        .map(|(mut key, c)| {
+            align(&mut key);
+            (key, c)
+        })
+        .collect();
+    (keys, key_char_used)
+}
+
+/// Keep only the keys made up entirely of printable characters
+///
+/// # Arguments
+///   * `keys`: The keys to filter
+///
+/// # Returns
+///   The keys whose bytes are all in the `printable` charset, in the same order
+fn printable_keys(keys: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+    keys.into_iter()
+        .filter(|key| key.iter().all(|&byte| is_printable_key_byte(byte)))
+        .collect()
+}
+
+/// Check whether a key byte is allowed by `--printable-key` and
+/// `--guess-only-printable-keys`
+///
+/// # Arguments
+///   * `byte`: The key byte to check
+///
+/// # Returns
+///   True if the byte is in the `printable` charset
+fn is_printable_key_byte(byte: u8) -> bool {
+    PREDEFINED_CHARSETS["printable"].as_bytes().contains(&byte)
+}
+
+/// Drop the keys made up entirely of zero bytes
+///
+/// # Arguments
+///   * `keys`: The keys to filter
+///
+/// # Returns
+///   The keys with at least one non-zero byte, in the same order
+fn non_null_keys(keys: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+    keys.into_iter()
+        .filter(|key| key.iter().any(|&byte| byte != 0))
+        .collect()
+}
+
+/// Sort keys so the ones giving the most valid plaintext come first
+///
+/// # Arguments
+///   * `ciphertext`: The encrypted data
+///   * `keys`: The keys to sort
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The keys sorted by descending percentage of valid characters. Keys
+///   with the same percentage keep their original order.
+fn sort_keys_by_validity(
+    ciphertext: &[u8],
+    keys: Vec<Vec<u8>>,
+    param: &Parameters,
+) -> Vec<Vec<u8>> {
+    let mut scored: Vec<(f64, Vec<u8>)> = keys
+        .into_iter()
+        .map(|key| (percentage_valid(&dexor(ciphertext, &key), param), key))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().map(|(_, key)| key).collect()
+}
+
+/// Product all combinations of possible key chars
+///
+/// # Arguments
+///   * `key_possible_bytes`: Vector of vectors, where each sub-vector
+///     is a set of characers used in a key
+///   * `key_part`: Portion of a possible key
+///   * `offset`: Offset into `key_possible_bytes` to loop over.
+///   * `max_keys`: Maximum number of keys to produce. Generation stops
+///     once this many keys have been produced.
+///
+/// # Warning
+///   This function is recursive
+///
+/// # Returns
+///   Vector of vectors of the possible key combinations
+fn all_keys(
+    key_possible_bytes: &Vec<Vec<u8>>,
+    key_part: &[u8],
+    offset: usize,
+    max_keys: usize,
+) -> Vec<Vec<u8>> {
+    let mut keys = Vec::new();
+    if max_keys == 0 {
+        return keys;
+    }
+    if offset >= key_possible_bytes.len() {
+        return Vec::from([key_part.to_vec()]);
+    }
+    for c in &key_possible_bytes[offset] {
+        let mut tmp = key_part.to_vec();
+        tmp.push(*c);
+        keys.extend(all_keys(
+            key_possible_bytes,
+            &tmp,
+            offset + 1,
+            max_keys - keys.len(),
+        ));
+        if keys.len() >= max_keys {
+            break;
+        }
+    }
+    keys
+}
+
+/// Count the keys `all_keys` would produce without a limit
+///
+/// # Arguments
+///   * `key_possible_bytes`: The possible bytes at each offset of the key
+///
+/// # Returns
+///   The number of key combinations, saturating at `usize::MAX`
+fn count_key_combinations(key_possible_bytes: &[Vec<u8>]) -> usize {
+    key_possible_bytes
+        .iter()
+        .fold(1, |count, bytes| count.saturating_mul(bytes.len()))
+}
+
+/// Print where the decryptions with two of the guessed keys differ
+///
+/// # Arguments
+///   * `ciphertext`: The encrypted data
+///   * `keys`: The keys that the tool has guessed
+///   * `index_a`: Index of the first key to compare
+///   * `index_b`: Index of the second key to compare
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if an index is not one of the guessed keys
+fn print_comparison(
+    ciphertext: &[u8],
+    keys: &[Vec<u8>],
+    index_a: usize,
+    index_b: usize,
+) -> Result<(), XorError> {
+    let line_end = line_end();
+    let (Some(key_a), Some(key_b)) = (keys.get(index_a), keys.get(index_b)) else {
+        return Err(XorError::ArgParser {
+            msg: format!(
+                "--compare {index_a} {index_b}: only {} key(s) were guessed",
+                keys.len()
+            ),
+        });
+    };
+    let differences = diff_decryptions(ciphertext, key_a, key_b);
+    message!(
+        "Decryptions with keys {index_a} and {index_b} differ at {}{}{} offset(s):{line_end}",
+        *C_COUNT,
+        differences.len(),
+        *C_RESET
+    );
+    for (offset, byte_a, byte_b) in differences {
+        message!("  {offset:#010x}: {byte_a:02x} {byte_b:02x}{line_end}");
+    }
+    Ok(())
+}
+
+/// Find where the decryptions with two keys differ
+///
+/// # Arguments
+///   * `ciphertext`: The encrypted data
+///   * `key_a`: The first key
+///   * `key_b`: The second key
+///
+/// # Returns
+///   The offset, byte decrypted with `key_a`, and byte decrypted with `key_b`
+///   at each offset where the decryptions differ
+fn diff_decryptions(ciphertext: &[u8], key_a: &[u8], key_b: &[u8]) -> Vec<(usize, u8, u8)> {
+    dexor(ciphertext, key_a)
+        .into_iter()
+        .zip(dexor(ciphertext, key_b))
+        .enumerate()
+        .filter(|&(_, (byte_a, byte_b))| byte_a != byte_b)
+        .map(|(offset, (byte_a, byte_b))| (offset, byte_a, byte_b))
+        .collect()
+}
+
+/// Print out all of the keys that the tool has guessed
+///
+/// # Arguments
+///   * `keys`: The keys that the tool has guessed.
+///   * `param`: Command line parameters, for how to display the keys.
+///
+/// # Errors
+///   Returns `XorError::IO` if writing raw keys to stdout fails.
+fn print_keys(keys: &[Vec<u8>], param: &Parameters) -> Result<(), XorError> {
+    let line_end = line_end();
+    if param.print0 {
+        let mut stdout = io::stdout().lock();
+        write_keys_print0(keys, &mut stdout)?;
+        stdout.flush()?;
+        return Ok(());
+    }
+    if keys.is_empty() {
+        message!("No keys guessed!{line_end}");
+        return Ok(());
+    }
+    message!(
+        "{}{}{} possible key(s) of length {}{}{}:{line_end}",
+        *C_COUNT,
+        keys.len(),
+        *C_RESET,
+        *C_COUNT,
+        keys[0].len(),
+        *C_RESET
+    );
+
+    for key in keys.iter().take(5) {
+        match param.key_format {
+            KeyFormat::Raw => {
+                // Raw keys are meant for piping, so skip the color codes.
+                let mut stdout = io::stdout();
+                stdout.write_all(key)?;
+                stdout.write_all(line_end.as_bytes())?;
+            }
+            KeyFormat::Escaped | KeyFormat::Hex => message!("{}{line_end}", key_line(key, param)),
+        }
+        let period = key_period(key);
+        if param.key_period && period < key.len() {
+            message!(
+                "  {}Repeats every {period} bytes: {}{}{line_end}",
+                *C_WARN,
+                to_key_literal(&key[..period], param.key_lang),
+                *C_RESET
+            );
+        }
+    }
+    if keys.len() > 10 {
+        message!("...{line_end}");
+    }
+    Ok(())
+}
+
+/// Write all of the keys as raw bytes, each followed by a NUL byte
+///
+/// Keys can hold any byte, including line breaks, so this is the only
+/// listing a script can split safely, like `find -print0`.
+///
+/// # Arguments
+///   * `keys`: The keys that the tool has guessed
+///   * `out`: Where to write the keys
+///
+/// # Errors
+///   Returns any error from writing the keys
+fn write_keys_print0(keys: &[Vec<u8>], out: &mut impl io::Write) -> io::Result<()> {
+    for key in keys {
+        out.write_all(key)?;
+        out.write_all(b"\0")?;
+    }
+    Ok(())
+}
+
+/// Find the smallest repeating unit of a key
+///
+/// A key such as `XYXYXY` decrypts the same as `XY`, so its effective
+/// length is shorter than the key length it was guessed at.
+///
+/// # Arguments
+///   * `key`: The key to check
+///
+/// # Returns
+///   Length of the shortest prefix that the key is made of repeats of
+fn key_period(key: &[u8]) -> usize {
+    (1..key.len())
+        .find(|&period| {
+            key.len().is_multiple_of(period)
+                && key.chunks(period).all(|unit| unit == &key[..period])
+        })
+        .unwrap_or(key.len())
+}
+
+/// Format a key for printing
+///
+/// # Arguments
+///   * `key`: The key to format
+///   * `param`: Command line parameters, for how to display the key. With
+///     `key_both`, the escaped key is followed by its hex form.
+///
+/// # Returns
+///   The colored key, without a line ending
+fn key_line(key: &[u8], param: &Parameters) -> String {
+    if param.key_both {
+        format!(
+            "{}{}{}  ({})",
+            *C_KEY,
+            to_escaped_key(key, param.key_lang),
+            *C_RESET,
+            to_hex_key(key)
+        )
+    } else {
+        format!(
+            "{}{}{}",
+            *C_KEY,
+            String::from_utf8_lossy(&render_key(key, param.key_format, param.key_lang)),
+            *C_RESET
+        )
+    }
+}
+
+/// Render a key in the given format
+///
+/// # Arguments
+///   * `bytes`: The bytes of the key to render
+///   * `key_format`: The format to render the key in
+///   * `key_lang`: The language to escape the key for
+///
+/// # Returns
+///   The bytes of the rendered key.
+fn render_key(bytes: &[u8], key_format: KeyFormat, key_lang: KeyLang) -> Vec<u8> {
+    match key_format {
+        KeyFormat::Escaped => to_escaped_key(bytes, key_lang).into_bytes(),
+        KeyFormat::Hex => to_hex_key(bytes).into_bytes(),
+        KeyFormat::Raw => bytes.to_vec(),
+    }
+}
+
+/// Convert a key into a lowercase hex string
+///
+/// # Arguments
+///   * `bytes`: The bytes of the key to convert
+///
+/// # Returns
+///   The key as a hex string, with no separators.
+fn to_hex_key(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(result, "{byte:02x}").unwrap();
+    }
+    result
+}
+
+/// Pick the quote character Python's `repr` would use for a byte string
+///
+/// # Arguments
+///   * `bytes`: The bytes of the key
+///
+/// # Returns
+///   `"` if the key contains a single quote but no double quote, otherwise `'`
+fn key_quote(bytes: &[u8]) -> char {
+    if bytes.contains(&b'\'') && !bytes.contains(&b'"') {
+        '"'
+    } else {
+        '\''
+    }
+}
+
+/// Convert a key into printable format
+///
+/// This matches the body of Python's `repr` for a byte string, so only
+/// the quote character chosen by `key_quote` is escaped.
+///
+/// # Arguments
+///   * `bytes`: The bytes of the key to compute
+///
+/// # Returns
+///   The key in a printable/displayable format.
+fn to_printable_key(bytes: &[u8]) -> String {
+    let quote = key_quote(bytes);
+    let mut result = String::new();
+    for &byte in bytes {
+        if (byte == b'\'' || byte == b'"') && char::from(byte) != quote {
+            result.push(char::from(byte));
+        } else {
+            for c in escape_default(byte) {
+                result.push(c.into());
+            }
+        }
+    }
+    result
+}
+
+/// Escape a key for the inside of a byte string literal
+///
+/// # Arguments
+///   * `bytes`: The bytes of the key
+///   * `key_lang`: The language of the literal
+///
+/// # Returns
+///   The escaped key, without the quotes
+fn to_escaped_key(bytes: &[u8], key_lang: KeyLang) -> String {
+    match key_lang {
+        KeyLang::Python => to_printable_key(bytes),
+        KeyLang::Rust => bytes.escape_ascii().to_string(),
+        // Hex escapes in C take as many digits as follow, so escape every byte
+        KeyLang::C => {
+            let mut result = String::with_capacity(bytes.len() * 4);
+            for byte in bytes {
+                write!(result, "\\x{byte:02x}").unwrap();
+            }
+            result
+        }
+    }
+}
+
+/// Convert a key into a byte string literal (e.g. `b'key'` for Python)
+///
+/// # Arguments
+///   * `bytes`: The bytes of the key
+///   * `key_lang`: The language of the literal
+///
+/// # Returns
+///   The key as a byte string literal that can be pasted into `key_lang`.
+fn to_key_literal(bytes: &[u8], key_lang: KeyLang) -> String {
+    let body = to_escaped_key(bytes, key_lang);
+    match key_lang {
+        KeyLang::Python => {
+            let quote = key_quote(bytes);
+            format!("b{quote}{body}{quote}")
+        }
+        KeyLang::Rust => format!("b\"{body}\""),
+        KeyLang::C => format!("\"{body}\""),
+    }
+}
+
+// -----------------------------------------------------------------------------
+// RETURNS PERCENTAGE OF VALID TEXT CHARS
+// -----------------------------------------------------------------------------
+/// Calculate the percentage of characters in the text that are within the charset in use
+///
+/// # Arguments
+///   * `text`: The text to check
+///   * `param`: The parameters to use
+///
+/// With `utf8_score` set, bytes of valid multi-byte UTF-8 characters are
+/// also valid, while ASCII bytes still have to be in the charset. With
+/// `wide` set, zero high bytes of 16-bit characters are valid. With
+/// `charset_ignore_case` set, ASCII letters are valid in either case.
+///
+/// # Returns
+///   Percentage of characters in `text` that are within the charset
+fn percentage_valid(text: &[u8], param: &Parameters) -> f64 {
+    let mut x = 0.0;
+    if param.utf8_score {
+        for chunk in text.utf8_chunks() {
+            for character in chunk.valid().chars() {
+                if !character.is_ascii()
+                    || u8::try_from(character).is_ok_and(|byte| in_charset(byte, param))
+                {
+                    x += f64::from(u8::try_from(character.len_utf8()).unwrap());
+                }
+            }
+        }
+    } else {
+        for (position, c) in text.iter().enumerate() {
+            // Zero high bytes of 16-bit characters are part of the text
+            let wide_zero = *c == 0
+                && param
+                    .wide
+                    .is_some_and(|endian| is_high_byte(position, endian));
+            if in_charset(*c, param) || wide_zero {
+                x += 1.0;
+            }
+        }
+    }
+    #[expect(
+        clippy::cast_precision_loss,
+        clippy::as_conversions,
+        reason = "Need to convert to float for division."
+    )]
+    {
+        x / (text.len() as f64)
+    }
+}
+
+/// Check whether a byte is in the charset in use
+///
+/// # Arguments
+///   * `byte`: The byte to check
+///   * `param`: The parameters to use. With `charset_ignore_case` set, either
+///     case of an ASCII letter is enough.
+///
+/// # Returns
+///   True if the byte is in `text_charset`
+fn in_charset(byte: u8, param: &Parameters) -> bool {
+    if param.charset_ignore_case {
+        param.text_charset.contains(&byte.to_ascii_lowercase())
+            || param.text_charset.contains(&byte.to_ascii_uppercase())
+    } else {
+        param.text_charset.contains(&byte)
+    }
+}
+
+/// Charsets compared by `--charset-report`
+const REPORT_CHARSETS: [&str; 4] = ["printable", "base32", "base64", "ascii"];
+
+/// Print how well the best plaintext fits each of the `REPORT_CHARSETS`
+///
+/// # Arguments
+///   * `ciphertext`: The encrypted data
+///   * `keys`: The keys that the tool has guessed
+///   * `param`: The parameters to use. The best plaintext is the one with the
+///     most bytes in its `text_charset`.
+fn print_charset_report(ciphertext: &[u8], keys: &[Vec<u8>], param: &Parameters) {
+    let line_end = line_end();
+    let best = keys
+        .iter()
+        .map(|key| {
+            let plaintext = dexor(ciphertext, key);
+            (percentage_valid(&plaintext, param), plaintext)
+        })
+        .enumerate()
+        .max_by(|(_, (a, _)), (_, (b, _))| a.total_cmp(b));
+    let Some((index, (_, plaintext))) = best else {
+        return;
+    };
+    message!("Charset fit of the best plaintext (key {index}):{line_end}");
+    for (name, fit) in charset_fits(&plaintext) {
+        message!(
+            "  {name:<9}: {}{:5.1}%{}{line_end}",
+            *C_COUNT,
+            fit * 100.0,
+            *C_RESET
+        );
+    }
+}
+
+/// Calculate the fraction of a text that is in each of the `REPORT_CHARSETS`
+///
+/// # Arguments
+///   * `text`: The text to check
+///
+/// # Returns
+///   The name of each charset, and the fraction of `text` in it
+fn charset_fits(text: &[u8]) -> Vec<(&'static str, f64)> {
+    REPORT_CHARSETS
+        .iter()
+        .map(|&name| {
+            let mut in_charset = [false; 256];
+            for byte in PREDEFINED_CHARSETS[name].bytes() {
+                in_charset[usize::from(byte)] = true;
+            }
+            let valid = text
+                .iter()
+                .filter(|&&byte| in_charset[usize::from(byte)])
+                .count();
+            #[expect(
+                clippy::cast_precision_loss,
+                clippy::as_conversions,
+                reason = "Need to convert to float for division."
+            )]
+            (name, valid as f64 / text.len() as f64)
+        })
+        .collect()
+}
+
+// -----------------------------------------------------------------------------
+// PRODUCE OUTPUT
+// -----------------------------------------------------------------------------
+
+/// Produce plaintext variant for each possible key,
+/// creates csv files with keys, percentage of valid
+/// characters and used most frequent character
+///
+/// The files are written to `out_dir` in `output`, which is created if needed.
+fn produce_plaintext(
+    ciphertext: &[u8],
+    keys: &[Vec<u8>],
+    key_char_used: &HashMap<Vec<u8>, u8>,
+    param: &Parameters,
+    out_dir: &str,
+    report: Option<&str>,
+    output: &mut Output,
+) -> Result<(), XorError> {
+    output.prepare_dir(out_dir)?;
+
+    let line_end = line_end();
+
+    let mut key_mapping = Vec::new();
+    let mut perc_mapping = Vec::new();
+
+    let source = csv_source(param);
+
+    let threshold_valid = param.threshold.unwrap_or(95);
+
+    let mut count_valid = 0;
+    let mut count_printable_headers = 0;
+    let mut best_candidate: Option<(i32, String)> = None;
+    let mut report_rows = String::new();
+    let out_ext = param.out_ext.as_deref().unwrap_or("out");
+    let mut shown = Vec::new();
+
+    for (index, key) in keys.iter().enumerate() {
+        let key_index = format!(
+            "{index:0>width$}",
+            width = format!("{}", (keys.len() - 1)).len(),
+        );
+        let file_name = format!("{out_dir}{MAIN_SEPARATOR}{key_index}.{out_ext}");
+
+        // ignore saving file when known plain is provided and output doesn't contain it
+        let Some(scored) = score_plaintext(ciphertext, key, param) else {
+            continue;
+        };
+        let dexored = scored.plaintext;
+        let perc = scored.perc;
+        if scored.passes_threshold {
+            count_valid += 1;
+        }
+        if best_candidate
+            .as_ref()
+            .is_none_or(|&(best_perc, _)| perc > best_perc)
+        {
+            best_candidate = Some((perc, key_index.clone()));
+        }
+        key_mapping.extend(csv_row(
+            source.as_deref(),
+            &[file_name.as_bytes(), &key_repr(key, param)],
+            param,
+        ));
+        write!(
+            report_rows,
+            "  {key_index}.{out_ext}: key {} char {:#04x} {perc}% valid{line_end}",
+            to_key_literal(key, param.key_lang),
+            key_char_used[key]
+        )
+        .unwrap();
+        let printable_header = param
+            .first_n_bytes_printable
+            .map(|length| has_printable_header(&dexored, length));
+        if printable_header == Some(true) {
+            count_printable_headers += 1;
+        }
+        perc_mapping.extend(perc_row(
+            source.as_deref(),
+            &file_name,
+            key_char_used[key],
+            perc,
+            printable_header,
+            param,
+        ));
+        if !param.filter_output || scored.passes_threshold {
+            output.write(&file_name, &dexored)?;
+            if param.show_best.is_some() {
+                let link = link_file(output, &file_name, &format!("{key_index}.{out_ext}"));
+                shown.push((perc, link, dexored));
+            }
+        }
+    }
+    write_mapping_csvs(
+        output,
+        out_dir,
+        source.is_some(),
+        &key_mapping,
+        &perc_mapping,
+        param,
+    )?;
+
+    let summary = summary_message(count_valid, threshold_valid, count_printable_headers, param);
+    message!("{summary}{line_end}");
+    if let Some(count) = param.show_best {
+        message!("{}", format_best_plaintexts(shown, count));
+    }
+    if let Some(report) = report {
+        let report_footer = format!(
+            "Found {count_valid} plaintexts with {threshold_valid}%+ valid characters{line_end}"
+        );
+        write_report(output, out_dir, report, &report_rows, &report_footer)?;
+    }
+    if param.retain_below_threshold
+        && let Some(line) = best_candidate_line(count_valid, best_candidate.as_ref())
+    {
+        message!("{line}{line_end}");
+    }
+    if !param.quiet {
+        print_csv_names(output, out_dir, KEY_MAPPING_CSV, PERC_MAPPING_CSV);
+    }
+
+    Ok(())
+}
+
+/// A plaintext decrypted with a guessed key, and how valid it is
+pub(crate) struct ScoredPlaintext {
+    /// The decrypted data
+    pub plaintext: Vec<u8>,
+    /// Fraction (0 to 1) of the plaintext that is in the charset
+    pub percentage_valid: f64,
+    /// `percentage_valid` as a rounded percentage
+    pub perc: i32,
+    /// Whether `perc` is above the threshold
+    pub passes_threshold: bool,
+}
+
+/// Decrypt the data with a key and score the plaintext
+///
+/// # Arguments
+///   * `ciphertext`: The encrypted data
+///   * `key`: The key to decrypt with
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The scored plaintext, or `None` if it doesn't contain the known plaintext
+pub(crate) fn score_plaintext(
+    ciphertext: &[u8],
+    key: &[u8],
+    param: &Parameters,
+) -> Option<ScoredPlaintext> {
+    let plaintext = dexor(ciphertext, key);
+    if !has_known_plain(&plaintext, param) {
+        return None;
+    }
+    let percentage_valid = percentage_valid(&plaintext, param);
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::as_conversions,
+        reason = "Value is always between 0 and 100, so casting rounded value to i32 is safe."
+    )]
+    let perc = (100.0 * percentage_valid).round() as i32;
+    Some(ScoredPlaintext {
+        plaintext,
+        percentage_valid,
+        perc,
+        passes_threshold: perc > param.threshold.unwrap_or(95),
+    })
+}
+
+/// Write `report.txt`
+///
+/// # Arguments
+///   * `output`: Where the output is written
+///   * `out_dir`: Directory the report goes in
+///   * `header`: The report up to the per-key table
+///   * `key_rows`: One row per key
+///   * `footer`: The summary after the per-key table
+///
+/// # Errors
+///   Returns any error from writing the report
+fn write_report(
+    output: &mut Output,
+    out_dir: &str,
+    header: &str,
+    key_rows: &str,
+    footer: &str,
+) -> Result<(), XorError> {
+    let line_end = line_end();
+    output.write(
+        &format!("{out_dir}{MAIN_SEPARATOR}{REPORT_TXT}"),
+        format!("{header}Keys:{line_end}{key_rows}{line_end}{footer}").as_bytes(),
+    )
+}
+
+/// Check if a plaintext contains the known plaintext, if one was given
+///
+/// # Arguments
+///   * `dexored`: The decrypted data
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   True if no known plaintext was given, or `dexored` contains it
+fn has_known_plain(dexored: &[u8], param: &Parameters) -> bool {
+    param.known_plain.as_ref().is_none_or(|known_plain| {
+        dexored
+            .windows(known_plain.len())
+            .any(|window| window == known_plain.as_slice())
+    })
+}
+
+/// Build a row of the file name to char used and score mapping CSV
+///
+/// # Arguments
+///   * `source`: Value of the source column, if there is one
+///   * `file_name`: Name of the file the plaintext is in
+///   * `char_used`: The most frequent char the key was guessed with
+///   * `perc`: Percentage of valid characters in the plaintext
+///   * `printable_header`: Whether the plaintext starts with printable
+///     characters, if that was checked
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The row, ending with a line break
+fn perc_row(
+    source: Option<&str>,
+    file_name: &str,
+    char_used: u8,
+    perc: i32,
+    printable_header: Option<bool>,
+    param: &Parameters,
+) -> Vec<u8> {
+    // FIXME: SHould be repr(key_char_used[key])
+    let char_used = char_used.to_string();
+    let perc = perc.to_string();
+    let mut fields = vec![file_name.as_bytes(), char_used.as_bytes(), perc.as_bytes()];
+    fields.extend(printable_header.map(|printable| -> &[u8] {
+        if printable { b"true" } else { b"false" }
+    }));
+    csv_row(source, &fields, param)
+}
+
+/// Check whether a plaintext starts with printable characters
+///
+/// # Arguments
+///   * `plaintext`: The decrypted data
+///   * `length`: Number of bytes at the start to check
+///
+/// # Returns
+///   Whether the first `length` bytes (or all of a shorter plaintext) are in
+///   the `printable` charset
+fn has_printable_header(plaintext: &[u8], length: usize) -> bool {
+    let printable = PREDEFINED_CHARSETS["printable"].as_bytes();
+    plaintext
+        .iter()
+        .take(length)
+        .all(|byte| printable.contains(byte))
+}
+
+/// Write the CSVs mapping the output files to their keys and scores
+///
+/// # Arguments
+///   * `output`: Where the output is written
+///   * `out_dir`: Directory the CSVs go in
+///   * `has_source`: Whether the rows have a source column
+///   * `key_mapping`: Rows of the file name to key mapping
+///   * `perc_mapping`: Rows of the file name to char used and score mapping
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Errors
+///   Returns any error from writing the CSVs
+fn write_mapping_csvs(
+    output: &mut Output,
+    out_dir: &str,
+    has_source: bool,
+    key_mapping: &[u8],
+    perc_mapping: &[u8],
+    param: &Parameters,
+) -> Result<(), XorError> {
+    let source_header = has_source.then_some("source");
+    let mut perc_columns: Vec<&[u8]> = vec![b"file_name", b"char_used", b"perc_valid"];
+    if param.first_n_bytes_printable.is_some() {
+        perc_columns.push(b"printable_header");
+    }
+    output.write_csv(
+        &format!("{out_dir}{MAIN_SEPARATOR}{KEY_MAPPING_CSV}"),
+        &csv_row(source_header, &[b"file_name", b"key_repr"], param),
+        key_mapping,
+        param.append_csv,
+    )?;
+    output.write_csv(
+        &format!("{out_dir}{MAIN_SEPARATOR}{PERC_MAPPING_CSV}"),
+        &csv_row(source_header, &perc_columns, param),
+        perc_mapping,
+        param.append_csv,
+    )
+}
+
+/// Get the source column of the CSVs
+///
+/// Rows say which input they came from when appended with `--append-csv`,
+/// or when standard input was given a name with `--stdin-name`.
+///
+/// # Arguments
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The value of the source column, or `None` when there is no source column
+fn csv_source(param: &Parameters) -> Option<String> {
+    (param.append_csv || param.stdin_name.is_some()).then(|| input_names(param).join(","))
+}
+
+/// Build a row of the CSVs
+///
+/// Without `--csv-delimiter`, fields are separated by `;` and never quoted,
+/// like the Python xortool. With it, fields holding the delimiter, a quote
+/// or a line break are quoted, doubling any quotes inside them.
+///
+/// # Arguments
+///   * `source`: Value of the source column, if there is one
+///   * `fields`: The other fields of the row
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The row, ending with a line break
+fn csv_row(source: Option<&str>, fields: &[&[u8]], param: &Parameters) -> Vec<u8> {
+    let delimiter = param.csv_delimiter.unwrap_or(b';');
+    let mut row = Vec::new();
+    for (index, field) in source.map(str::as_bytes).iter().chain(fields).enumerate() {
+        if index > 0 {
+            row.push(delimiter);
+        }
+        let needs_quotes = param.csv_delimiter.is_some()
+            && field
+                .iter()
+                .any(|byte| [delimiter, b'"', b'\r', b'\n'].contains(byte));
+        if needs_quotes {
+            row.push(b'"');
+            for &byte in *field {
+                if byte == b'"' {
+                    row.push(b'"');
+                }
+                row.push(byte);
+            }
+            row.push(b'"');
+        } else {
+            row.extend_from_slice(field);
+        }
+    }
+    row.extend_from_slice(line_end().as_bytes());
+    row
+}
+
+/// Get the names of the input files for labeling the output
+///
+/// # Arguments
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The input file names, with `-` replaced by the `--stdin-name` if one
+///   was given
+fn input_names(param: &Parameters) -> Vec<&str> {
+    param
+        .filenames
+        .iter()
+        .map(|filename| match &param.stdin_name {
+            Some(stdin_name) if filename == "-" => stdin_name.as_str(),
+            _ => filename.as_str(),
+        })
+        .collect()
+}
+
+/// Print where the CSV files of a run were written
+///
+/// # Arguments
+///   * `output`: The output the files were written to
+///   * `out_dir`: The directory the files are in
+///   * `fn_key_mapping`: File name of the key mapping CSV
+///   * `fn_perc_mapping`: File name of the percentage mapping CSV
+fn print_csv_names(output: &Output, out_dir: &str, fn_key_mapping: &str, fn_perc_mapping: &str) {
+    let key_path = format!("{out_dir}{MAIN_SEPARATOR}{fn_key_mapping}");
+    let perc_path = format!("{out_dir}{MAIN_SEPARATOR}{fn_perc_mapping}");
+    // Output is not in the usual place, so give the full paths
+    let (key_text, perc_text) = if out_dir == DIRNAME {
+        (fn_key_mapping, fn_perc_mapping)
+    } else {
+        (key_path.as_str(), perc_path.as_str())
+    };
+    message!(
+        "See files {}, {}{}",
+        link_file(output, &key_path, key_text),
+        link_file(output, &perc_path, perc_text),
+        line_end()
+    );
+}
+
+/// Display an output file name, linking to the file in supporting terminals
+///
+/// # Arguments
+///   * `output`: The output the file was written to
+///   * `path`: Path of the file
+///   * `text`: Text to display for the file
+///
+/// # Returns
+///   The text, wrapped in a hyperlink to the file if possible
+fn link_file(output: &Output, path: &str, text: &str) -> String {
+    output
+        .file_url(path)
+        .map_or_else(|| text.to_owned(), |url| hyperlink(&url, text))
+}
+
+/// Get how a key is shown in the file name to key mapping CSV
+///
+/// # Arguments
+///   * `key`: The key used
+///   * `param`: Command line parameters, for how to display the key
+///
+/// # Returns
+///   The key as a literal when escaped, otherwise rendered in `key_format`
+fn key_repr(key: &[u8], param: &Parameters) -> Vec<u8> {
+    match param.key_format {
+        KeyFormat::Escaped => to_key_literal(key, param.key_lang).into_bytes(),
+        KeyFormat::Hex | KeyFormat::Raw => render_key(key, param.key_format, param.key_lang),
+    }
+}
+
+/// Build the start of `report.txt`, describing the key length analysis
+///
+/// # Arguments
+///   * `fitnesses`: Ranked key length candidates, empty if the key length
+///     was given instead of guessed
+///   * `key_lengths`: The key lengths being tried
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The report text up to the per-key table
+fn report_header(fitnesses: &[(i32, f64)], key_lengths: &[i32], param: &Parameters) -> String {
+    let line_end = line_end();
+    let mut report = format!("xortool {VERSION} report{line_end}{line_end}");
+    write!(report, "Input: {}{line_end}", input_names(param).join(", ")).unwrap();
+    write!(
+        report,
+        "Parameters:{line_end}{}{line_end}",
+        parameter_lines(key_lengths, param)
+    )
+    .unwrap();
+    if fitnesses.is_empty() {
+        write!(report, "Key length was given, not guessed{line_end}").unwrap();
+    } else {
+        write!(report, "Key length candidates:{line_end}").unwrap();
+        let fitness_sum = calc_fitness_sum(fitnesses);
+        for (key_length, fitness) in fitnesses {
+            write!(
+                report,
+                "  {key_length}: {:.1}%{line_end}",
+                100.0 * fitness / fitness_sum
+            )
+            .unwrap();
+        }
+        for divisor in shown_divisors(&common_divisors(fitnesses), param) {
+            write!(report, "Key-length can be {divisor}*n{line_end}").unwrap();
+        }
+    }
+    let key_lengths: Vec<String> = key_lengths.iter().map(i32::to_string).collect();
+    write!(
+        report,
+        "Key lengths tried: {}{line_end}{line_end}",
+        key_lengths.join(", ")
+    )
+    .unwrap();
+    report
+}
+
+/// Build the summary of how many plaintexts reached the threshold
+///
+/// # Arguments
+///   * `count_valid`: Number of plaintexts that reached the threshold
+///   * `threshold_valid`: Percentage of valid characters needed to count
+///   * `count_printable_headers`: Number of plaintexts starting with
+///     printable characters, only reported with `first_n_bytes_printable`
+///   * `param`: Command line parameters supplied to the tool
+///
+/// # Returns
+///   The summary, without a line ending after the last line
+fn summary_message(
+    count_valid: i32,
+    threshold_valid: i32,
+    count_printable_headers: i32,
+    param: &Parameters,
+) -> String {
+    let mut msg = format!(
+        "Found {}{count_valid}{} plaintexts with {}{threshold_valid}{}%+ valid characters",
+        *C_COUNT, *C_RESET, *C_COUNT, *C_RESET
+    );
+    if param.known_plain.is_some() {
+        write!(
+            msg,
+            " which contained '{}'",
+            String::from_utf8(param.known_plain.clone().unwrap()).unwrap()
+        )
+        .unwrap();
+    }
+    if param.first_n_bytes_printable.is_some() {
+        write!(
+            msg,
+            "{}{}{count_printable_headers}{} plaintexts had printable headers",
+            line_end(),
+            *C_COUNT,
+            *C_RESET
+        )
+        .unwrap();
+    }
+    msg
+}
+
+/// Format the most valid plaintexts for printing
+///
+/// # Arguments
+///   * `plaintexts`: Percentage of valid characters, file name, and bytes of
+///     each plaintext
+///   * `count`: Number of plaintexts to format
+///
+/// # Returns
+///   The `count` most valid plaintexts, each with a header line giving its
+///   file name and percentage of valid characters, with non-printable bytes
+///   escaped.
+fn format_best_plaintexts(mut plaintexts: Vec<(i32, String, Vec<u8>)>, count: usize) -> String {
+    let line_end = line_end();
+    plaintexts.sort_by_key(|&(perc, _, _)| Reverse(perc));
+    let mut result = String::new();
+    for (perc, file_name, plaintext) in plaintexts.iter().take(count) {
+        write!(
+            result,
+            "{}----- {file_name} ({perc}% valid) -----{}{line_end}{}{line_end}",
+            *C_DIV,
+            *C_RESET,
+            to_printable_key(plaintext)
+        )
+        .unwrap();
+    }
+    result
+}
+
+/// Describe the best candidate when no plaintext reached the threshold
+///
+/// # Arguments
+///   * `count_valid`: Number of plaintexts that reached the threshold
+///   * `best_candidate`: Highest percentage of valid characters seen, and
+///     the index of the file it was seen in
+///
+/// # Returns
+///   The line to print, or `None` if some plaintext reached the threshold
+///   or there were no candidates at all.
+fn best_candidate_line(count_valid: i32, best_candidate: Option<&(i32, String)>) -> Option<String> {
+    if count_valid > 0 {
+        return None;
+    }
+    best_candidate.map(|(perc, key_index)| {
+        format!(
+            "Best candidate scored {}{perc}{}% (index {key_index})",
+            *C_COUNT, *C_RESET
+        )
+    })
+}
+
+/// Find the first numbered run directory that does not exist yet
+///
+/// # Returns
+///   Path of the form `xortool_out/run_N` that can be used for the output
+///
+/// # Errors
+///   Returns an error if checking for existing directories failed
+fn fresh_run_dir() -> Result<String, XorError> {
+    let mut run = 1;
+    loop {
+        let run_dir = format!("{DIRNAME}{MAIN_SEPARATOR}run_{run}");
+        if !fs::exists(&run_dir)? {
+            return Ok(run_dir);
+        }
+        run += 1;
+    }
+}
+
+/// Delete the output directory if it already exists.
+///
+/// # Errors
+///   Returns an error if the directory could not be deleted, or if it was
+///   not created by xortool and holds files xortool does not write
+fn cleanup() -> Result<(), XorError> {
+    rmdir(DIRNAME, is_known_output)
+}
+
+/// Check whether a file name is one xortool writes into `DIRNAME`
+///
+/// # Arguments
+///   * `name`: The file name to check
+///
+/// # Returns
+///   True for the default `.out` plaintexts, the CSV files, and the report
+fn is_known_output(name: &str) -> bool {
+    Path::new(name).extension().is_some_and(|ext| ext == "out")
+        || [KEY_MAPPING_CSV, PERC_MAPPING_CSV, REPORT_TXT].contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+    use crate::{
+        charset::get_charset_bytes,
+        error::{EXIT_ANALYSIS, EXIT_ARGUMENT, EXIT_IO, EXIT_TIMEOUT},
+    };
+
+    #[test]
+    fn test_get_ciphertext() {
+        let param = Parameters {
+            filenames: vec![String::from("tests/small_file.txt")],
+            ..Default::default()
+        };
+        assert_eq!(&*get_ciphertext(&param).unwrap(), "Hello World!".as_bytes())
+    }
+
+    #[test]
+    fn test_get_ciphertext_multiple_files() {
+        let param = Parameters {
+            filenames: vec![
+                String::from("tests/small_file.txt"),
+                String::from("tests/small_file_hex.txt"),
+            ],
+            ..Default::default()
+        };
+        let combined = get_ciphertext(&param).unwrap();
+        let first = fs::read("tests/small_file.txt").unwrap();
+        let second = fs::read("tests/small_file_hex.txt").unwrap();
+        assert_eq!(combined.len(), first.len() + second.len());
+        assert!(combined.starts_with(&first));
+    }
+
+    #[test]
+    fn test_analysis_window() {
+        let param = Parameters {
+            skip_head: 2,
+            skip_tail: 3,
+            ..Default::default()
+        };
+        assert_eq!(analysis_window(b"0123456789", &param).unwrap(), b"23456");
+        assert_eq!(
+            analysis_window(b"0123456789", &Parameters::default()).unwrap(),
+            b"0123456789"
+        );
+    }
+
+    #[test]
+    fn test_analysis_window_too_large() {
+        let param = Parameters {
+            skip_head: 6,
+            skip_tail: 4,
+            ..Default::default()
+        };
+        assert!(matches!(
+            analysis_window(b"0123456789", &param),
+            Err(XorError::ArgParser { .. })
+        ));
+    }
+
+    #[test]
+    fn test_align_keys_decrypts_full_buffer() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let key = b"secret!";
+        let ciphertext = dexor(&plaintext, key);
+        let param = Parameters {
+            skip_head: 12,
+            skip_tail: 5,
+            known_key_length: Some(7),
+            ..Default::default()
+        };
+        let window = analysis_window(&ciphertext, &param).unwrap();
+        assert_eq!(window.len(), ciphertext.len() - 17);
+
+        let (keys, key_char_used) = guess_probable_keys_for_chars(window, b" ", &param).unwrap();
+        let (keys, key_char_used) = align_keys(keys, key_char_used, param.skip_head);
+        assert_eq!(keys, vec![key.to_vec()]);
+        assert_eq!(key_char_used[&key.to_vec()], b' ');
+        assert_eq!(dexor(&ciphertext, &keys[0]), plaintext);
+    }
+
+    #[test]
+    fn test_best_candidate_line_below_threshold() {
+        let line = best_candidate_line(0, Some(&(87, String::from("042")))).unwrap();
+        assert!(line.starts_with("Best candidate scored "));
+        assert!(line.contains("87"));
+        assert!(line.ends_with("% (index 042)"));
+    }
+
+    #[test]
+    fn test_best_candidate_line_above_threshold() {
+        assert_eq!(best_candidate_line(1, Some(&(99, String::from("0")))), None);
+        assert_eq!(best_candidate_line(0, None), None);
+    }
+
+    #[test]
+    fn test_column_entropies() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let ciphertext = dexor(&plaintext, b"secret!");
+        // XOR with a single byte doesn't change a column's entropy
+        let at_key_length = column_entropies(&ciphertext, 7);
+        for (cipher, plain) in at_key_length.iter().zip(column_entropies(&plaintext, 7)) {
+            assert!((cipher - plain).abs() < 1e-9);
+        }
+        // Compare the mean entropy of 7 columns against the mean of 5 columns
+        let at_wrong_length: f64 = column_entropies(&ciphertext, 5).iter().sum();
+        assert!(at_key_length.iter().sum::<f64>() * 5.0 < at_wrong_length * 7.0);
+    }
+
+    #[test]
+    fn test_default_max_key_length_small_file() {
+        assert_eq!(default_max_key_length(10), 2);
+        assert_eq!(default_max_key_length(2), 2);
+    }
+
+    #[test]
+    fn test_rank_key_lengths_without_max_key_length() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let ciphertext = dexor(&plaintext, b"secret!");
+        let param = Parameters {
+            max_key_length: None,
+            ..Default::default()
+        };
+        let fitnesses = rank_key_lengths(&ciphertext, &param).unwrap();
+        assert_eq!(pick_key_length(&fitnesses, &param), 7);
+        assert!(
+            fitnesses
+                .iter()
+                .all(|&(length, _)| length <= DEFAULT_MAX_KEY_LENGTH)
+        );
+
+        let zero = Parameters {
+            max_key_length: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            rank_key_lengths(&ciphertext, &zero),
+            Err(XorError::Analysis {
+                msg: String::from("Maximum key length must be at least 1, got 0")
+            })
+        );
+    }
+
+    #[test]
+    fn test_default_max_key_length_large_file() {
+        assert_eq!(default_max_key_length(96324), 65);
+        assert_eq!(default_max_key_length(260), 65);
+    }
+
+    #[test]
+    fn test_key_length_sample() {
+        let param = Parameters {
+            sample_bytes: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(key_length_sample(b"0123456789", &param), b"0123");
+        assert_eq!(key_length_sample(b"01", &param), b"01");
+        assert_eq!(
+            key_length_sample(b"0123456789", &Parameters::default()),
+            b"0123456789"
+        );
+    }
+
+    #[test]
+    fn test_guess_key_length_uses_sample() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        // The first 2000 bytes use a 3 byte key, the rest a 7 byte key
+        let mut ciphertext = dexor(&plaintext[..2000], b"k3y");
+        ciphertext.extend(dexor(&plaintext[2000..], b"secret!"));
+        let param = Parameters {
+            max_key_length: Some(65),
+            quiet: true,
+            sample_bytes: Some(2000),
+            ..Default::default()
+        };
+        let sample = key_length_sample(&ciphertext, &param);
+        assert_eq!(guess_key_length(sample, &param).unwrap().0, 3);
+        assert_eq!(guess_key_length(&ciphertext, &param).unwrap().0, 7);
+    }
+
+    #[test]
+    fn test_guess_key_length_fitness_out() {
+        let path = "tests/fitness_out.tsv";
+        let ciphertext = dexor(&fs::read("tests/english.txt").unwrap(), b"secret!");
+        let param = Parameters {
+            max_key_length: Some(65),
+            quiet: true,
+            fitness_out: Some(String::from(path)),
+            ..Default::default()
+        };
+        let (key_length, fitnesses) = guess_key_length(&ciphertext, &param).unwrap();
+        let table = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+        let rows: Vec<&str> = table.lines().collect();
+        assert!(fitnesses.len() > 10);
+        assert_eq!(rows.len(), fitnesses.len() + 1);
+        assert_eq!(rows[0], "key_length\tfitness\tprobability");
+        assert!(rows[1].starts_with(&format!("{key_length}\t")));
+    }
+
+    #[test]
+    fn test_key_length_from_pct() {
+        let text_len = fs::read("tests/small_file.txt").unwrap().len();
+        assert_eq!(text_len, 12);
+        assert_eq!(key_length_from_pct(text_len, 25.0), Ok(3));
+        assert_eq!(key_length_from_pct(text_len, 20.0), Ok(2));
+        assert_eq!(key_length_from_pct(text_len, 100.0), Ok(12));
+        assert_eq!(
+            key_length_from_pct(text_len, 1.0),
+            Err(XorError::ArgParser {
+                msg: String::from(
+                    "--keylen-pct 1 gives key length 0, but it must be between 1 and 12"
+                )
+            })
+        );
+        key_length_from_pct(text_len, 150.0).unwrap_err();
+        key_length_from_pct(text_len, f64::NAN).unwrap_err();
+        Parameters::try_parse_from(["xortool", "--keylen-pct", "10", "-l", "3"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_is_near_constant() {
+        assert!(is_near_constant(&[0; 1000]));
+        let mut mostly_zero = vec![0; 1000];
+        mostly_zero[500] = 1;
+        assert!(is_near_constant(&mostly_zero));
+        assert!(!is_near_constant(&fs::read("tests/english.txt").unwrap()));
+        assert!(!is_near_constant(b""));
+
+        let filename = "tests/all_zero.bin";
+        fs::write(filename, [0; 1000]).unwrap();
+        let args = ["xortool", "-c", "20", filename]
+            .iter()
+            .map(|&arg| String::from(arg))
+            .collect();
+        let code = main(Some(args));
+        fs::remove_file(filename).unwrap();
+        assert_eq!(code, ExitCode::FAILURE);
+    }
+
+    #[test]
+    fn test_guess_key_length_short_file() {
+        // Repeats with period 3, so 3 is the best key length
+        let ciphertext = b"q7Zq7Z";
+        let param = Parameters {
+            max_key_length: Some(65),
+            ..Default::default()
+        };
+        // Still guesses a length, just with a warning printed
+        let key_length = guess_key_length(ciphertext, &param).unwrap().0;
+        assert_eq!(key_length, 3);
+        assert!(is_too_short_for_key_length(ciphertext.len(), key_length));
+        assert!(is_too_short_for_key_length(6, 3));
+        assert!(!is_too_short_for_key_length(6, 2));
+        assert!(!is_too_short_for_key_length(2954, 8));
+    }
+
+    #[test]
+    fn test_calculate_fitnesses_multiple_of() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let ciphertext = dexor(&plaintext, b"eight!!!");
+        let param = Parameters {
+            max_key_length: Some(65),
+            multiple_of: Some(4),
+            ..Default::default()
+        };
+        let fitnesses = calculate_fitnesses(&ciphertext, &param);
+        assert!(!fitnesses.is_empty());
+        assert!(
+            fitnesses.iter().all(|&(length, _)| length % 4 == 0),
+            "{fitnesses:?}"
+        );
+        assert!(fitnesses.iter().any(|&(length, _)| length == 8));
+    }
+
+    #[test]
+    fn test_calculate_fitnesses_single_multiple() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let ciphertext = dexor(&plaintext, b"secret!");
+        let lengths = |max_key_length: i32, multiple_of: Option<i32>| {
+            let param = Parameters {
+                max_key_length: Some(max_key_length),
+                multiple_of,
+                ..Default::default()
+            };
+            rank_key_lengths(&ciphertext, &param)
+                .unwrap()
+                .into_iter()
+                .map(|(length, _)| length)
+                .collect::<Vec<i32>>()
+        };
+        assert_eq!(lengths(65, Some(40)), vec![40]);
+        assert_eq!(lengths(10, Some(8)), vec![8]);
+        assert_eq!(lengths(1, None), vec![1]);
+
+        let too_large = Parameters {
+            max_key_length: Some(65),
+            multiple_of: Some(80),
+            ..Default::default()
+        };
+        assert!(matches!(
+            rank_key_lengths(&ciphertext, &too_large),
+            Err(XorError::ArgParser { .. })
+        ));
+    }
+
+    #[test]
+    fn test_calculate_fitnesses_prefer_length() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let ciphertext = dexor(&plaintext, b"secret!");
+        let best = |param: &Parameters| {
+            calculate_fitnesses(&ciphertext, param)
+                .into_iter()
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap()
+                .0
+        };
+        let unbiased = Parameters {
+            max_key_length: Some(65),
+            ..Default::default()
+        };
+        assert_eq!(best(&unbiased), 7);
+
+        // 14 is the runner-up to 7, so a strong enough boost lets it win
+        let near_tie = Parameters {
+            prefer_length: Some(14),
+            prefer_boost: Some(1.5),
+            ..unbiased
+        };
+        assert_eq!(best(&near_tie), 14);
+
+        // 12 fits far worse than 7, so the hint doesn't override the data
+        let mismatch = Parameters {
+            prefer_length: Some(12),
+            ..near_tie
+        };
+        assert_eq!(best(&mismatch), 7);
+        assert!((preferred_fitness(24, 2.0, &mismatch) - 3.0).abs() < f64::EPSILON);
+        assert!((preferred_fitness(7, 2.0, &mismatch) - 2.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_autocorrelation_peaks_at_period() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let ciphertext = dexor(&plaintext, b"period");
+        let param = Parameters {
+            max_key_length: Some(20),
+            autocorr: true,
+            ..Default::default()
+        };
+        let fitnesses = rank_key_lengths(&ciphertext, &param).unwrap();
+        let peaks: Vec<i32> = fitnesses.iter().take(3).map(|&(shift, _)| shift).collect();
+        assert!(peaks.contains(&6), "{fitnesses:?}");
+        assert!(peaks.contains(&12), "{fitnesses:?}");
+        assert!(peaks.contains(&18), "{fitnesses:?}");
+        assert!(autocorrelation(&ciphertext, 6) > 2.0 * autocorrelation(&ciphertext, 5));
+        assert_eq!(pick_key_length(&fitnesses, &param), 6);
+    }
+
+    #[test]
+    fn test_autocorrelation_short_text() {
+        assert!(autocorrelation(b"ab", 2).abs() < f64::EPSILON);
+        assert!(autocorrelation(b"ab", 5).abs() < f64::EPSILON);
+        assert!((autocorrelation(b"aaa", 1) - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_multiple_of_rejects_zero() {
+        Parameters::try_parse_from(["xortool", "--multiple-of", "0"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_diff_decryptions() {
+        let ciphertext = dexor(b"Hello World!", b"abc");
+        assert_eq!(
+            diff_decryptions(&ciphertext, b"abc", b"aBc"),
+            vec![
+                (1, b'e', b'e' ^ b'b' ^ b'B'),
+                (4, b'o', b'o' ^ b'b' ^ b'B'),
+                (7, b'o', b'o' ^ b'b' ^ b'B'),
+                (10, b'd', b'd' ^ b'b' ^ b'B'),
+            ]
+        );
+        assert!(diff_decryptions(&ciphertext, b"abc", b"abc").is_empty());
+    }
+
+    #[test]
+    fn test_compare_index_out_of_range() {
+        let keys = vec![b"abc".to_vec()];
+        print_comparison(b"data", &keys, 0, 1).unwrap_err();
+        Parameters::try_parse_from(["xortool", "--compare", "0", "-"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_guess_probable_keys_for_chars_duplicates() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let ciphertext = dexor(&plaintext, b"k3y");
+        let all_chars: Vec<u8> = (0..=255).collect();
+        // A fully known seed key gives the same key for every char
+        let seeded = Parameters {
+            known_key_length: Some(3),
+            seed_key: Some(vec![Some(b'k'), Some(b'3'), Some(b'y')]),
+            ..Default::default()
+        };
+        let (keys, key_char_used) =
+            guess_probable_keys_for_chars(&ciphertext, &all_chars, &seeded).unwrap();
+        assert_eq!(keys, vec![b"k3y".to_vec()]);
+        assert_eq!(key_char_used[b"k3y".as_slice()], 255);
+
+        // Same keys, in the same order, as checking the list for each key
+        let param = Parameters {
+            known_key_length: Some(3),
+            ..Default::default()
+        };
+        let (probable_keys, chars_used) =
+            guess_probable_keys_for_chars(&ciphertext, &all_chars, &param).unwrap();
+        let mut expected: Vec<Vec<u8>> = Vec::new();
+        for &c in &all_chars {
+            for key in guess_keys(&ciphertext, c, &param).unwrap() {
+                if !expected.contains(&key) {
+                    expected.push(key);
+                }
+            }
+        }
+        assert_eq!(probable_keys, expected);
+        assert_eq!(chars_used.len(), probable_keys.len());
+    }
+
+    #[test]
+    fn test_auto_char() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let ciphertext = dexor(&plaintext, b"k3y!");
+        let mut param = Parameters::try_parse_from(["xortool", "--auto-char", "-"]).unwrap();
+        let try_chars = most_frequent_chars(&param).unwrap();
+        assert_eq!(try_chars, b" ");
+        param.known_key_length = Some(4);
+        let (keys, _) = guess_probable_keys_for_chars(&ciphertext, &try_chars, &param).unwrap();
+        assert_eq!(keys, vec![b"k3y!".to_vec()]);
+    }
+
+    #[test]
+    fn test_auto_char_conflicts_with_char() {
+        Parameters::try_parse_from(["xortool", "--auto-char", "-c", "20", "-"]).unwrap_err();
+        assert_eq!(most_frequent_chars(&Parameters::default()), None);
+    }
+
+    #[test]
+    fn test_guess_keys_seed_key() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let key = b"secret!!";
+        let ciphertext = dexor(&plaintext, key);
+        // First half fixed, with a wrong byte to show the seed is used as-is
+        let seed_key = vec![
+            Some(b's'),
+            Some(b'E'),
+            Some(b'c'),
+            Some(b'r'),
+            None,
+            None,
+            None,
+            None,
+        ];
+        let param = Parameters {
+            known_key_length: Some(8),
+            seed_key: Some(seed_key),
+            ..Default::default()
+        };
+        let keys = guess_keys(&ciphertext, b' ', &param).unwrap();
+        assert!(keys.iter().all(|guessed| guessed.starts_with(b"sEcr")));
+        assert!(keys.iter().any(|guessed| guessed == b"sEcret!!"));
+    }
+
+    #[test]
+    fn test_guess_keys_printable_seed() {
+        let ciphertext = dexor(&fs::read("tests/english.txt").unwrap(), b"k3y");
+        let mut param = Parameters {
+            known_key_length: Some(3),
+            seed_key: Some(vec![Some(0x01), None, None]),
+            guess_only_printable_keys: true,
+            ..Default::default()
+        };
+        // The seed byte is pruned like a guessed one
+        assert_eq!(guess_keys(&ciphertext, b' ', &param), Ok(Vec::new()));
+        param.seed_key = Some(vec![Some(b'k'), None, None]);
+        let keys = guess_keys(&ciphertext, b' ', &param).unwrap();
+        assert!(keys.contains(&b"k3y".to_vec()));
+        assert!(
+            keys.iter()
+                .flatten()
+                .all(|&byte| is_printable_key_byte(byte))
+        );
+    }
+
+    #[test]
+    fn test_guess_keys_wide() {
+        let plaintext: Vec<u8> = fs::read_to_string("tests/english.txt")
+            .unwrap()
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let ciphertext = dexor(&plaintext, b"k3y");
+        let mut param = Parameters {
+            known_key_length: Some(3),
+            text_charset: get_charset_bytes("printable").unwrap(),
+            ..Default::default()
+        };
+        // Half of the bytes are zero, so space is not the most common byte
+        assert!(
+            !guess_keys(&ciphertext, b' ', &param)
+                .unwrap()
+                .contains(&b"k3y".to_vec())
+        );
+        param.wide = Some(Endian::Le);
+        assert_eq!(
+            guess_keys(&ciphertext, b' ', &param).unwrap(),
+            vec![b"k3y".to_vec()]
+        );
+        // With the doubled key length, each column holds only low or high bytes
+        param.known_key_length = Some(6);
+        assert_eq!(
+            guess_keys(&ciphertext, b' ', &param).unwrap(),
+            vec![b"k3yk3y".to_vec()]
+        );
+        assert!((percentage_valid(&plaintext, &param) - 1.0).abs() < f64::EPSILON);
+
+        let wide_param = Parameters::try_parse_from(["xortool", "--wide", "file.bin"]).unwrap();
+        assert_eq!(wide_param.wide, Some(Endian::Le));
+        let be_param = Parameters::try_parse_from(["xortool", "--wide=be"]).unwrap();
+        assert_eq!(be_param.wide, Some(Endian::Be));
+    }
+
+    #[test]
+    fn test_guess_probable_keys_keep_going() {
+        let ciphertext = dexor(&fs::read("tests/english.txt").unwrap(), b"k3y");
+        let mut param = Parameters {
+            known_key_length: Some(3),
+            ..Default::default()
+        };
+        let failing_guess = |text: &[u8], most_char: u8, guess_param: &Parameters| {
+            if most_char == b'e' {
+                Err(XorError::Analysis {
+                    msg: String::from("injected"),
+                })
+            } else {
+                guess_keys(text, most_char, guess_param)
+            }
+        };
+        assert_eq!(
+            guess_probable_keys_with(&ciphertext, b"e ", &param, failing_guess),
+            Err(XorError::Analysis {
+                msg: String::from("injected")
+            })
+        );
+        param.keep_going = true;
+        let (keys, key_char_used) =
+            guess_probable_keys_with(&ciphertext, b"e ", &param, failing_guess).unwrap();
+        assert!(keys.contains(&b"k3y".to_vec()));
+        assert!(key_char_used.values().all(|&c| c == b' '));
+    }
+
+    #[test]
+    fn test_guess_probable_keys_total_cap() {
+        let param =
+            Parameters::try_parse_from(["xortool", "--max-total-candidate-keys", "4"]).unwrap();
+        let three_keys = |_: &[u8], most_char: u8, _: &Parameters| {
+            Ok(vec![
+                vec![most_char],
+                vec![most_char, 1],
+                vec![most_char, 2],
+            ])
+        };
+        let (keys, key_char_used) =
+            guess_probable_keys_with(b"", b"abc", &param, three_keys).unwrap();
+        assert_eq!(keys, [vec![b'a'], vec![b'a', 1], vec![b'a', 2], vec![b'b']]);
+        assert_eq!(key_char_used.len(), 4);
+        Parameters::try_parse_from(["xortool", "--max-total-candidate-keys", "0"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_guess_keys_no_data() {
+        let param = Parameters {
+            known_key_length: Some(8),
+            ..Default::default()
+        };
+        assert_eq!(
+            guess_keys(b"short", b' ', &param),
+            Err(XorError::Analysis {
+                msg: String::from(
+                    "No data to guess key offset 5 from: key length 8 is longer than the 5 bytes analyzed"
+                )
+            })
+        );
+    }
+
+    #[test]
+    fn test_phase_timings() {
+        let timings = PhaseTimings {
+            key_length: Duration::from_millis(1500),
+            key_guessing: Duration::from_micros(20),
+            plaintext: Duration::ZERO,
+        };
+        let breakdown = timings.breakdown();
+        assert_eq!(breakdown.lines().count(), 4);
+        assert!(breakdown.contains("   key length guessing: 1500.000 ms"));
+        assert!(breakdown.contains("          key guessing: 0.020 ms"));
+        assert!(breakdown.contains("  plaintext production: 0.000 ms"));
+        let param = Parameters::try_parse_from(["xortool", "--benchmark"]).unwrap();
+        assert!(param.benchmark);
+    }
+
+    #[test]
+    fn test_guess_keys_capped() {
+        // Every byte of each column is different, so all of them are tied
+        let ciphertext: Vec<u8> = (0..=255).collect();
+        let mut param =
+            Parameters::try_parse_from(["xortool", "--max-candidates-per-char", "10"]).unwrap();
+        param.known_key_length = Some(4);
+        assert_eq!(param.max_candidate_keys, 10);
+        assert_eq!(Parameters::default().max_candidate_keys, 1000);
+        Parameters::try_parse_from([
+            "xortool",
+            "--max-candidate-keys",
+            "10",
+            "--unlimited-candidate-keys",
+        ])
+        .unwrap_err();
+        let keys = guess_keys(&ciphertext, b' ', &param).unwrap();
+        assert_eq!(keys.len(), 10);
+        assert_eq!(
+            count_key_combinations(&[vec![0; 64], vec![0; 64], vec![0; 64], vec![0; 64]]),
+            64 * 64 * 64 * 64
+        );
+    }
+
+    #[test]
+    fn test_guess_keys_only_printable() {
+        // Every byte of each column is different, so all of them are tied
+        let ciphertext: Vec<u8> = (0..=255).collect();
+        let all_param = Parameters {
+            known_key_length: Some(2),
+            unlimited_candidate_keys: true,
+            ..Default::default()
+        };
+        let all = guess_keys(&ciphertext, b' ', &all_param).unwrap();
+        let mut pruned_param = Parameters::try_parse_from([
+            "xortool",
+            "--guess-only-printable-keys",
+            "--unlimited-candidate-keys",
+        ])
+        .unwrap();
+        pruned_param.known_key_length = Some(2);
+        let pruned = guess_keys(&ciphertext, b' ', &pruned_param).unwrap();
+        assert_eq!(all.len(), 128 * 128);
+        assert!(!pruned.is_empty());
+        assert!(pruned.len() < all.len());
+        assert_eq!(printable_keys(pruned.clone()), pruned);
+
+        // A column without any printable key byte gives no keys at all
+        let unprintable = [0x20, 0x20, 0x20, 0x20];
+        pruned_param.known_key_length = Some(1);
+        assert_eq!(
+            guess_keys(&unprintable, b' ', &pruned_param),
+            Ok(Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_seed_key_lengths() {
+        let seed_key = [Some(b'a'), None, None];
+        assert_eq!(seed_key_lengths(&seed_key, &[]), Ok(vec![3]));
+        assert_eq!(seed_key_lengths(&seed_key, &[3]), Ok(vec![3]));
+        assert!(matches!(
+            seed_key_lengths(&seed_key, &[3, 4]),
+            Err(XorError::ArgParser { .. })
+        ));
+    }
+
+    /// Files written by a `produce_plaintext` run in a test
+    struct Produced {
+        /// The output directory, as it appears in the CSV files
+        out_dir: String,
+        /// Contents of each file in the output directory, by file name
+        files: HashMap<String, String>,
+    }
+
+    /// Run `produce_plaintext` in a fresh temporary directory
+    ///
+    /// Every key is recorded as found with the space character. The
+    /// directory is removed before returning, so failing assertions in the
+    /// caller leave nothing behind.
+    ///
+    /// # Arguments
+    ///   * `name`: Name for the run's directory
+    ///   * `ciphertext`: Ciphertext to decrypt
+    ///   * `keys`: Keys to decrypt with
+    ///   * `params`: Parameters for each run into the same directory
+    ///   * `report`: Header for the report, if one is written
+    ///
+    /// # Returns
+    ///   The output directory and the files written to it
+    fn produce_in_temp_dir(
+        name: &str,
+        ciphertext: &[u8],
+        keys: &[Vec<u8>],
+        params: &[&Parameters],
+        report: Option<&str>,
+    ) -> Produced {
+        let out_dir = env::temp_dir()
+            .join(format!("xortool_{name}_{}", process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let key_char_used = keys.iter().map(|key| (key.clone(), b' ')).collect();
+        let result = params.iter().try_for_each(|param| {
+            produce_plaintext(
+                ciphertext,
+                keys,
+                &key_char_used,
+                param,
+                &out_dir,
+                report,
+                &mut Output::Directory,
+            )
+        });
+        let files = fs::read_dir(&out_dir)
+            .map(|entries| {
+                entries
+                    .map(|entry| {
+                        let entry = entry.unwrap();
+                        let contents = fs::read(entry.path()).unwrap();
+                        (
+                            entry.file_name().to_string_lossy().into_owned(),
+                            String::from_utf8_lossy(&contents).into_owned(),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        // There is no directory if producing failed early
+        if fs::exists(&out_dir).unwrap() {
+            fs::remove_dir_all(&out_dir).unwrap();
+        }
+        result.unwrap();
+        Produced { out_dir, files }
+    }
+
+    #[test]
+    fn test_produce_plaintext_report() {
+        let param = Parameters {
+            filenames: vec![String::from("hello.bin")],
+            max_key_length: Some(65),
+            text_charset: PREDEFINED_CHARSETS["printable"].bytes().collect(),
+            quiet: true,
+            report: true,
+            ..Default::default()
+        };
+        let fitnesses = vec![(2, 3.0), (4, 1.0)];
+        let header = report_header(&fitnesses, &[2], &param);
+        let produced = produce_in_temp_dir(
+            "report",
+            &dexor(b"Hello World", b"ab"),
+            &[b"ab".to_vec()],
+            &[&param],
+            Some(&header),
+        );
+        let report = &produced.files["report.txt"];
+        assert!(report.contains("Input: hello.bin"));
+        assert!(report.contains("Parameters:"));
+        assert!(report.contains("  threshold: 95%"));
+        assert!(report.contains("  text charset: '0123456789abc"));
+        assert!(!report.contains("deadline"));
+        assert!(report.contains("  2: 75.0%"));
+        assert!(report.contains("Key lengths tried: 2"));
+        assert!(report.contains("0.out: key b'ab' char 0x20 100% valid"));
+        assert!(report.contains("Found 1 plaintexts with 95%+ valid characters"));
+    }
+
+    #[test]
+    fn test_produce_plaintext_append_csv() {
+        let params = ["first.bin", "second.bin"].map(|filename| Parameters {
+            filenames: vec![String::from(filename)],
+            max_key_length: Some(65),
+            text_charset: get_charset_bytes("printable").unwrap(),
+            quiet: true,
+            append_csv: true,
+            ..Default::default()
+        });
+        let produced = produce_in_temp_dir(
+            "append",
+            &dexor(b"Hello World", b"ab"),
+            &[b"ab".to_vec()],
+            &[&params[0], &params[1]],
+            None,
+        );
+        let out_dir = &produced.out_dir;
+        let key_rows: Vec<&str> = produced.files["filename-key.csv"].lines().collect();
+        assert_eq!(
+            key_rows,
+            [
+                "source;file_name;key_repr",
+                &format!("first.bin;{out_dir}{MAIN_SEPARATOR}0.out;b'ab'"),
+                &format!("second.bin;{out_dir}{MAIN_SEPARATOR}0.out;b'ab'"),
+            ]
+        );
+        let perc_csv = &produced.files["filename-char_used-perc_valid.csv"];
+        assert_eq!(perc_csv.matches("file_name").count(), 1);
+        assert!(perc_csv.contains(&format!("first.bin;{out_dir}{MAIN_SEPARATOR}0.out;32;100")));
+        assert!(perc_csv.contains(&format!("second.bin;{out_dir}{MAIN_SEPARATOR}0.out;32;100")));
+    }
+
+    #[test]
+    fn test_produce_plaintext_csv_delimiter() {
+        let param = Parameters {
+            max_key_length: Some(65),
+            text_charset: get_charset_bytes("printable").unwrap(),
+            quiet: true,
+            csv_delimiter: Some(b','),
+            ..Default::default()
+        };
+        let produced = produce_in_temp_dir(
+            "csv_delimiter",
+            &dexor(b"Hello World", b"a,"),
+            &[b"a,".to_vec()],
+            &[&param],
+            None,
+        );
+        let key_csv = &produced.files["filename-key.csv"];
+        let perc_csv = &produced.files["filename-char_used-perc_valid.csv"];
+
+        // Split the rows back into fields, honoring the quotes
+        let parse = |csv: &str| -> Vec<Vec<String>> {
+            csv.lines()
+                .map(|line| {
+                    let mut fields = vec![String::new()];
+                    let mut quoted = false;
+                    for character in line.chars() {
+                        match character {
+                            '"' => quoted = !quoted,
+                            ',' if !quoted => fields.push(String::new()),
+                            _ => fields.last_mut().unwrap().push(character),
+                        }
+                    }
+                    fields
+                })
+                .collect()
+        };
+        let file_name = format!("{}{MAIN_SEPARATOR}0.out", produced.out_dir);
+        assert_eq!(
+            parse(key_csv),
+            [
+                vec![String::from("file_name"), String::from("key_repr")],
+                vec![file_name.clone(), String::from("b'a,'")],
+            ]
+        );
+        assert_eq!(
+            parse(perc_csv),
+            [
+                vec![
+                    String::from("file_name"),
+                    String::from("char_used"),
+                    String::from("perc_valid")
+                ],
+                vec![file_name, String::from("32"), String::from("100")],
+            ]
+        );
+        assert!(key_csv.contains("\"b'a,'\""));
+
+        let quote = Parameters {
+            csv_delimiter: Some(b','),
+            ..Default::default()
+        };
+        assert_eq!(
+            csv_row(None, &[b"say \"hi\"", b"x"], &quote),
+            format!("\"say \"\"hi\"\"\",x{}", line_end()).into_bytes()
+        );
+    }
+
+    #[test]
+    fn test_produce_plaintext_printable_header() {
+        let plaintext = b"GIF89a header\x00\x01\x02 then binary data";
+        let param = Parameters {
+            max_key_length: Some(65),
+            text_charset: get_charset_bytes("printable").unwrap(),
+            quiet: true,
+            first_n_bytes_printable: Some(6),
+            ..Default::default()
+        };
+        // The second key sets the high bit of every byte
+        let produced = produce_in_temp_dir(
+            "printable_header",
+            &dexor(plaintext, b"k"),
+            &[b"k".to_vec(), b"\xeb".to_vec()],
+            &[&param],
+            None,
+        );
+        let perc_rows: Vec<&str> = produced.files["filename-char_used-perc_valid.csv"]
+            .lines()
+            .collect();
+        assert_eq!(
+            perc_rows[0],
+            "file_name;char_used;perc_valid;printable_header"
+        );
+        assert!(perc_rows[1].ends_with(";true"));
+        assert!(perc_rows[2].ends_with(";false"));
+
+        assert!(has_printable_header(b"GIF89a\x00", 6));
+        assert!(!has_printable_header(b"GIF89a\x00", 7));
+        assert!(has_printable_header(b"GIF", 6));
+    }
+
+    #[test]
+    fn test_produce_plaintext_stdin_name() {
+        let mut param =
+            Parameters::try_parse_from(["xortool", "--stdin-name", "capture-7", "-"]).unwrap();
+        param.max_key_length = Some(65);
+        param.quiet = true;
+        let produced = produce_in_temp_dir(
+            "stdin_name",
+            &dexor(b"Hello World", b"ab"),
+            &[b"ab".to_vec()],
+            &[&param],
+            None,
+        );
+        let key_csv = &produced.files["filename-key.csv"];
+        assert!(key_csv.starts_with("source;file_name;key_repr"));
+        assert!(key_csv.contains(&format!(
+            "capture-7;{}{MAIN_SEPARATOR}0.out;b'ab'",
+            produced.out_dir
+        )));
+        param.filenames = vec![String::from("a.bin"), String::from("-")];
+        assert_eq!(input_names(&param), ["a.bin", "capture-7"]);
+    }
+
+    #[test]
+    fn test_produce_plaintext_out_ext() {
+        let mut param = Parameters::try_parse_from(["xortool", "--out-ext", ".png"]).unwrap();
+        param.max_key_length = Some(65);
+        param.quiet = true;
+        let produced = produce_in_temp_dir(
+            "out_ext",
+            &dexor(b"Hello World", b"ab"),
+            &[b"ab".to_vec()],
+            &[&param],
+            None,
+        );
+        let plaintext_name = format!("{}{MAIN_SEPARATOR}0.png;", produced.out_dir);
+        assert_eq!(produced.files["0.png"], "Hello World");
+        assert!(produced.files["filename-key.csv"].contains(&plaintext_name));
+        assert!(produced.files["filename-char_used-perc_valid.csv"].contains(&plaintext_name));
+        Parameters::try_parse_from(["xortool", "--out-ext", "../x"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_is_known_output() {
+        for name in [
+            "0.out",
+            "12.out",
+            KEY_MAPPING_CSV,
+            PERC_MAPPING_CSV,
+            REPORT_TXT,
+        ] {
+            assert!(is_known_output(name), "{name}");
+        }
+        for name in ["notes.txt", "0.png", "run_1", ".xortool"] {
+            assert!(!is_known_output(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_percentage_valid_utf8() {
+        let mut param = Parameters {
+            text_charset: PREDEFINED_CHARSETS["printable"].bytes().collect(),
+            ..Default::default()
+        };
+        let text = "Gr\u{fc}\u{df}e, \u{4e16}\u{754c}!".as_bytes();
+        // Only the ASCII bytes are in the printable charset
+        assert!((percentage_valid(text, &param) - 6.0 / 16.0).abs() < f64::EPSILON);
+        param.utf8_score = true;
+        assert!((percentage_valid(text, &param) - 1.0).abs() < f64::EPSILON);
+
+        // A truncated character, a stray continuation byte and a control char
+        let broken = b"Gr\xc3\xbc\xc3e \x80\x01!";
+        assert!((percentage_valid(broken, &param) - 7.0 / 10.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_charset_fits() {
+        let fits = charset_fits(b"SGVsbG8gV29ybGQh\n");
+        let names: Vec<&str> = fits.iter().map(|&(name, _)| name).collect();
+        assert_eq!(names, REPORT_CHARSETS);
+        let expected_fits = [1.0, 8.0 / 17.0, 16.0 / 17.0, 1.0];
+        for (&(name, fit), expected) in fits.iter().zip(expected_fits) {
+            assert!((fit - expected).abs() < f64::EPSILON, "{name}: {fit}");
+        }
+    }
+
+    #[test]
+    fn test_format_best_plaintexts() {
+        let plaintexts = vec![
+            (50, String::from("0.out"), b"Hel\x01o".to_vec()),
+            (100, String::from("1.out"), b"Hello".to_vec()),
+            (75, String::from("2.out"), b"Hallo".to_vec()),
+        ];
+        let shown = format_best_plaintexts(plaintexts.clone(), 2);
+        assert_eq!(shown.matches("-----").count(), 4);
+        assert!(shown.find("1.out (100% valid)") < shown.find("2.out (75% valid)"));
+        assert!(!shown.contains("0.out"));
+        let all = format_best_plaintexts(plaintexts, 10);
+        assert_eq!(all.matches("-----").count(), 6);
+        assert!(all.contains("Hel\\x01o"));
+    }
+
+    /// Count equal characters by walking each offset with a stride, to check
+    /// `count_equals_transposed` against
+    fn reference_count_equals(text: &[u8], key_length: i32) -> i32 {
+        if usize::try_from(key_length).unwrap() >= text.len() {
+            return 0;
+        }
+        (0..key_length)
+            .map(|offset| {
+                chars_count_at_offset(text, key_length, offset)
+                    .values()
+                    .max()
+                    .unwrap()
+                    - 1
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_count_equals_transposed() {
+        assert_eq!(count_equals_transposed(b"Hello World!", 2), 1);
+        assert_eq!(count_equals_transposed(b"Hi", 4), 0);
+        let ciphertext = fs::read("test/data/binary_xored").unwrap();
+        for key_length in 1..=65 {
+            assert_eq!(
+                count_equals_transposed(&ciphertext, key_length),
+                reference_count_equals(&ciphertext, key_length),
+                "key length {key_length}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_percentage_valid_subtracted_charset() {
+        let text = b"one two\tthree\n";
+        let mut param = Parameters {
+            text_charset: get_charset_bytes("printable").unwrap(),
+            ..Default::default()
+        };
+        assert!((percentage_valid(text, &param) - 1.0).abs() < f64::EPSILON);
+        param.text_charset = get_charset_bytes("printable-\\t\\n ").unwrap();
+        assert!((percentage_valid(text, &param) - 11.0 / 14.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_column_bytes() {
+        let text = b"abcdefghij";
+        assert_eq!(column_bytes(text, 3, 0).collect::<Vec<u8>>(), b"adgj");
+        assert_eq!(column_bytes(text, 3, 1).collect::<Vec<u8>>(), b"beh");
+        assert_eq!(column_bytes(text, 3, 2).collect::<Vec<u8>>(), b"cfi");
+        // Every byte is in exactly one column
+        let mut all: Vec<u8> = (0..3)
+            .flat_map(|offset| column_bytes(text, 3, offset))
+            .collect();
+        all.sort_unstable();
+        assert_eq!(all, text);
+    }
+
+    #[test]
+    fn test_offset_ambiguity() {
+        // Offset 0 sees "aab", offset 1 sees "bcd"
+        assert_eq!(offset_ambiguity(b"abacbd", 2), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_key_confidence() {
+        // Offset 0 sees "aaab", offset 1 sees "bcdb", offset 2 sees nothing
+        let confidence = key_confidence(b"abacadbb", 2);
+        assert!((confidence[0] - 0.75).abs() < f64::EPSILON);
+        assert!((confidence[1] - 0.5).abs() < f64::EPSILON);
+        let short = key_confidence(b"a", 2);
+        assert!((short[0] - 1.0).abs() < f64::EPSILON);
+        assert!(short[1].abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_most_common_bytes() {
+        // Tied bytes are in the order they first occur
+        assert_eq!(most_common_bytes(b"adacbb", 2, 1), b"dcb");
+        assert_eq!(most_common_bytes(b"abacbd", 2, 0), b"a");
+    }
+
+    #[test]
+    fn test_guess_keys_stable_order() {
+        // Every byte of each column is different, so all of them are tied
+        let ciphertext: Vec<u8> = (0..=255).rev().collect();
+        let param = Parameters {
+            known_key_length: Some(2),
+            unlimited_candidate_keys: true,
+            ..Default::default()
+        };
+        let keys = guess_keys(&ciphertext, 0, &param).unwrap();
+        assert_eq!(keys.len(), 128 * 128);
+        assert_eq!(keys[0], vec![255, 254]);
+        assert_eq!(keys[1], vec![255, 252]);
+        for _ in 0..5 {
+            assert_eq!(guess_keys(&ciphertext, 0, &param).unwrap(), keys);
+        }
+    }
+
+    #[test]
+    fn test_all_keys() {
+        let key_possible_bytes = vec![vec![1, 2], vec![3], vec![4, 5]];
+        assert_eq!(
+            all_keys(&key_possible_bytes, &[], 0, 1000),
+            vec![vec![1, 3, 4], vec![1, 3, 5], vec![2, 3, 4], vec![2, 3, 5]]
+        );
+    }
+
+    #[test]
+    fn test_all_keys_capped() {
+        // 20 offsets with 4 tied bytes each would give 4^20 keys
+        let key_possible_bytes = vec![vec![1, 2, 3, 4]; 20];
+        assert_eq!(
+            count_key_combinations(&key_possible_bytes),
+            usize::pow(4, 20)
+        );
+        let keys = all_keys(&key_possible_bytes, &[], 0, 10);
+        assert_eq!(keys.len(), 10);
+        assert!(keys.iter().all(|key| key.len() == 20));
+    }
+
+    #[test]
+    fn test_key_line_both() {
+        let mut param = Parameters::try_parse_from(["xortool", "--key-both", "file.bin"]).unwrap();
+        let line = key_line(b"k\x00y", &param);
+        assert!(line.contains("k\\x00y"));
+        assert!(line.ends_with("  (6b0079)"));
+        param.key_both = false;
+        assert!(!key_line(b"k\x00y", &param).contains("6b0079"));
+        Parameters::try_parse_from(["xortool", "--key-both", "--key-format", "hex", "file.bin"])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_write_keys_print0() {
+        let keys = vec![b"k\n3y".to_vec(), b"\xffab".to_vec()];
+        let mut out = Vec::new();
+        write_keys_print0(&keys, &mut out).unwrap();
+        assert_eq!(out, b"k\n3y\0\xffab\0");
+        assert_eq!(
+            out.split(|&byte| byte == 0).collect::<Vec<&[u8]>>(),
+            [&b"k\n3y"[..], b"\xffab", b""]
+        );
+        Parameters::try_parse_from(["xortool", "--print0", "--key-format", "hex", "file.bin"])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_key_period() {
+        assert_eq!(key_period(b"XYXYXY"), 2);
+        assert_eq!(key_period(b"XYZXYZ"), 3);
+        assert_eq!(key_period(b"XXXX"), 1);
+        assert_eq!(key_period(b"XYXYX"), 5);
+        assert_eq!(key_period(b"secret!"), 7);
+        assert_eq!(key_period(b""), 0);
+
+        // A length 6 probe of a length 2 key recovers the key repeated
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let param = Parameters {
+            known_key_length: Some(6),
+            ..Default::default()
+        };
+        let keys = guess_keys(&dexor(&plaintext, b"XY"), b' ', &param).unwrap();
+        assert_eq!(keys, vec![b"XYXYXY".to_vec()]);
+        assert_eq!(key_period(&keys[0]), 2);
+    }
+
+    #[test]
+    fn test_echo_params() {
+        let param = Parameters::try_parse_from([
+            "xortool",
+            "-t",
+            "aA",
+            "-c",
+            "20",
+            "-l",
+            "7",
+            "--threshold",
+            "80",
+            "--echo-params",
+            "tests/enc.bin",
+        ])
+        .unwrap();
+        assert!(param.echo_params);
+        let echo = echo_params(&param.key_lengths, &param);
+        assert!(echo.starts_with("Resolved parameters:"));
+        assert!(echo.contains("  input: tests/enc.bin (raw)"));
+        assert!(echo.contains(&format!("  key length: 7{}", line_end())));
+        assert!(echo.contains("  most frequent char: ' '"));
+        assert!(echo.contains("  threshold: 80%"));
+        assert!(echo.contains("  score: weighted"));
+        let charset = format!(
+            "  text charset: '{}' (52 bytes)",
+            param.text_charset.escape_ascii()
+        );
+        assert!(echo.contains(&charset));
+
+        // A guessed key length is shown once it is known
+        let guessed_param = Parameters {
+            most_frequent_char: Some(b' '),
+            max_key_length: Some(65),
+            quiet: true,
+            ..Default::default()
+        };
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let (key_lengths, _) =
+            select_key_lengths(&dexor(&plaintext, b"secret!"), &guessed_param).unwrap();
+        let guessed = echo_params(&key_lengths, &guessed_param);
+        assert!(guessed.contains("  key length: 7 (guessed)"));
+        assert!(guessed.contains("  input: -"));
+    }
+
+    #[test]
+    fn test_printable_keys() {
+        let keys = vec![b"k3y".to_vec(), b"\x00\xffy".to_vec(), b"s e".to_vec()];
+        assert_eq!(printable_keys(keys), vec![b"k3y".to_vec(), b"s e".to_vec()]);
+    }
+
+    #[test]
+    fn test_non_null_keys() {
+        let keys = vec![b"\x00\x00".to_vec(), b"\x00y".to_vec(), b"k3".to_vec()];
+        assert_eq!(non_null_keys(keys), vec![b"\x00y".to_vec(), b"k3".to_vec()]);
+    }
+
+    #[test]
+    fn test_sort_keys_by_validity() {
+        let param = Parameters {
+            text_charset: b"abc".to_vec(),
+            ..Default::default()
+        };
+        let ciphertext = b"abcabc";
+        // Decrypts to 0/6, 6/6, and 4/6 valid characters
+        let keys = vec![vec![0xFF], vec![0x00], vec![0x00, 0x00, 0x7F]];
+        assert_eq!(
+            sort_keys_by_validity(ciphertext, keys, &param),
+            vec![vec![0x00], vec![0x00, 0x00, 0x7F], vec![0xFF]]
+        );
+    }
+
+    #[test]
+    fn test_main_inner_exit_codes() {
+        let run = |args: &[&str]| {
+            Parameters::try_parse_from(args)
+                .map_err(XorError::from)
+                .and_then(main_inner)
+                .unwrap_err()
+                .exit_code()
+        };
+        assert_eq!(run(&["xortool", "--no-such-flag"]), EXIT_ARGUMENT);
+        assert_eq!(run(&["xortool", "-t", "Q", "-c", "20"]), EXIT_ARGUMENT);
+        assert_eq!(run(&["xortool", "-c", "20", "tests/missing.bin"]), EXIT_IO);
+        assert_eq!(
+            // "Hel" has no repeated bytes, so there are no key length candidates
+            run(&[
+                "xortool",
+                "-c",
+                "20",
+                "--sample-bytes",
+                "3",
+                "tests/small_file.txt"
+            ]),
+            EXIT_ANALYSIS
+        );
+    }
+
+    #[test]
+    fn test_timeout() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let ciphertext = dexor(&plaintext, b"secret!");
+        let mut param = Parameters {
+            known_key_length: Some(7),
+            timeout: Some(Duration::ZERO),
+            deadline: Some(Instant::now()),
+            ..Default::default()
+        };
+        let timed_out = guess_probable_keys_for_chars(&ciphertext, b" e", &param).unwrap_err();
+        assert_eq!(
+            timed_out,
+            XorError::Timeout {
+                msg: String::from("Gave up after 0 seconds. Tried 0 of 2 chars, and found 0 keys")
+            }
+        );
+        param.deadline = None;
+        let (keys, _) = guess_probable_keys_for_chars(&ciphertext, b" e", &param).unwrap();
+        assert!(keys.contains(&b"secret!".to_vec()));
+
+        // Stops after guessing the key length, before anything is written
+        let args = ["xortool", "-c", "20", "--timeout", "0", "tests/english.txt"]
+            .iter()
+            .map(|&arg| String::from(arg))
+            .collect();
+        assert_eq!(main(Some(args)), ExitCode::from(EXIT_TIMEOUT));
+    }
+
+    #[test]
+    fn test_main_exit_codes() {
+        let args = |args: &[&str]| Some(args.iter().map(|&arg| String::from(arg)).collect());
+        // No most frequent char given
+        assert_eq!(
+            main(args(&["xortool", "tests/small_file.txt"])),
+            ExitCode::FAILURE
+        );
+        assert_eq!(
+            main(args(&["xortool", "-c", "20", "tests/missing.bin"])),
+            ExitCode::FAILURE
+        );
+        assert_eq!(
+            main(args(&[
+                "xortool",
+                "--detailed-exit-codes",
+                "-c",
+                "20",
+                "tests/missing.bin"
+            ])),
+            ExitCode::from(EXIT_IO)
+        );
+        // Argument errors only know about the flag from the raw command line
+        assert_eq!(
+            main(args(&[
+                "xortool",
+                "--detailed-exit-codes",
+                "--no-such-flag"
+            ])),
+            ExitCode::from(EXIT_ARGUMENT)
+        );
+        // After `--`, it is the name of a file
+        assert_eq!(
+            main(args(&[
+                "xortool",
+                "-c",
+                "20",
+                "--",
+                "--detailed-exit-codes"
+            ])),
+            ExitCode::FAILURE
+        );
     }
-    if keys.len() > 10 {
-        print!("...{line_end}");
+
+    #[test]
+    fn test_requests_detailed_exit_codes() {
+        let check = |args: &[&str]| {
+            let args: Vec<OsString> = args.iter().map(OsString::from).collect();
+            requests_detailed_exit_codes(&args)
+        };
+        assert!(check(&["xortool", "--detailed-exit-codes", "-x"]));
+        assert!(!check(&["xortool", "-x"]));
+        assert!(!check(&["xortool", "--", "--detailed-exit-codes"]));
+        assert!(!check(&["--detailed-exit-codes"]));
     }
-}
 
-/// Convert a key into printable format
-///
-/// # Arguments
-///   * `bytes`: The bytes of the key to compute
-///
-/// # Returns
-///   The key in a printable/displayable format.
-fn to_printable_key(bytes: &[u8]) -> String {
-    let mut result = String::new();
-    for &byte in bytes {
-        for c in escape_default(byte) {
-            result.push(c.into());
+    #[test]
+    fn test_only_key_length() {
+        // The output goes in the working directory, so the run is done by
+        // this test in a child process started in a fresh directory
+        if env::var_os("XORTOOL_ONLY_KEY_LENGTH_CHILD").is_some() {
+            let english = format!("{}/tests/english.txt", env!("CARGO_MANIFEST_DIR"));
+            let args = vec![
+                String::from("xortool"),
+                String::from("--only-key-length"),
+                english,
+            ];
+            // No most frequent char is needed
+            assert_eq!(main(Some(args)), ExitCode::SUCCESS);
+            return;
         }
+        let work_dir = env::temp_dir().join(format!("xortool_only_key_length_{}", process::id()));
+        fs::create_dir(&work_dir).unwrap();
+        let status = process::Command::new(env::current_exe().unwrap())
+            .args(["--exact", "tool_main::tests::test_only_key_length"])
+            .current_dir(&work_dir)
+            .env("XORTOOL_ONLY_KEY_LENGTH_CHILD", "1")
+            .stdout(process::Stdio::null())
+            .status()
+            .unwrap();
+        let created = fs::read_dir(&work_dir).unwrap().count();
+        fs::remove_dir_all(&work_dir).unwrap();
+        assert!(status.success());
+        assert_eq!(created, 0);
     }
-    // To match the original test, we don't want to escape the quote character.
-    result = result.replace("\\\"", "\"");
-    if result.contains('\'') && !result.contains('"') {
-        result.replace("\\'", "'")
-    } else {
-        result
-    }
-}
 
-// -----------------------------------------------------------------------------
-// RETURNS PERCENTAGE OF VALID TEXT CHARS
-// -----------------------------------------------------------------------------
-/// Calculate the percentage of characters in the text that are within the charset in use
-///
-/// # Arguments
-///   * `text`: The text to check
-///   * `param`: The parameters to use
-///
-/// # Returns
-///   Percentage of characters in `text` that are within the charset
-fn percentage_valid(text: &[u8], param: &Parameters) -> f64 {
-    let mut x = 0.0;
-    for c in text {
-        if param.text_charset.contains(c) {
-            x += 1.0;
-        }
+    #[test]
+    fn test_get_ciphertext_dash_filename() {
+        let dash_param = Parameters::try_parse_from(["xortool", "--", "-x"]).unwrap();
+        assert!(!dash_param.input_is_hex);
+        assert_eq!(dash_param.filenames, ["-x"]);
+
+        let work_dir = env::temp_dir().join(format!("xortool_dash_filename_{}", process::id()));
+        fs::create_dir_all(&work_dir).unwrap();
+        let filename = work_dir.join("-x").to_string_lossy().into_owned();
+        fs::write(&filename, "Hello World!").unwrap();
+        let param = Parameters::try_parse_from(["xortool", "--", &filename]).unwrap();
+        let ciphertext = get_ciphertext(&param).map(|data| data.to_vec());
+        fs::remove_dir_all(&work_dir).unwrap();
+        assert_eq!(ciphertext.unwrap(), b"Hello World!");
     }
-    #[expect(
-        clippy::cast_precision_loss,
-        clippy::as_conversions,
-        reason = "Need to convert to float for division."
-    )]
-    {
-        x / (text.len() as f64)
+
+    #[test]
+    fn test_get_ciphertext_stdin_with_files() {
+        let param = Parameters {
+            filenames: vec![String::from("tests/small_file.txt"), String::from("-")],
+            ..Default::default()
+        };
+        assert!(matches!(
+            get_ciphertext(&param),
+            Err(XorError::ArgParser { .. })
+        ));
     }
-}
 
-// -----------------------------------------------------------------------------
-// PRODUCE OUTPUT
-// -----------------------------------------------------------------------------
+    #[test]
+    fn test_get_ciphertext_hex() {
+        let param = Parameters {
+            filenames: vec![String::from("tests/small_file_hex.txt")],
+            input_is_hex: true,
+            ..Default::default()
+        };
+        assert_eq!(&*get_ciphertext(&param).unwrap(), "Hello World".as_bytes())
+    }
 
-/// Produce plaintext variant for each possible key,
-/// creates csv files with keys, percentage of valid
-/// characters and used most frequent character
-fn produce_plaintext(
-    ciphertext: &[u8],
-    keys: &[Vec<u8>],
-    key_char_used: &HashMap<Vec<u8>, u8>,
-    param: &Parameters,
-) -> Result<(), XorError> {
-    cleanup();
-    mkdir(DIRNAME)?;
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn test_get_ciphertext_gzip() {
+        let param = Parameters {
+            filenames: vec![String::from("tests/small_file.txt.gz")],
+            gzip_input: true,
+            ..Default::default()
+        };
+        assert_eq!(&*get_ciphertext(&param).unwrap(), "Hello World!".as_bytes());
+    }
 
-    let line_end = if env::consts::OS == "windows" {
-        "\r\n"
-    } else {
-        "\n"
-    };
+    #[test]
+    fn test_get_ciphertext_empty() {
+        let param = Parameters::try_parse_from(["xortool", "-x", "-"]).unwrap();
+        assert!(param.input_is_hex);
+        assert_eq!(
+            ensure_not_empty(FileData::Owned(Vec::new()), &param).unwrap_err(),
+            XorError::IO {
+                msg: String::from("No data was read from standard input")
+            }
+        );
+        assert_eq!(
+            &*ensure_not_empty(FileData::Owned(b"41\n".to_vec()), &param).unwrap(),
+            b"41\n"
+        );
 
-    // this is split up in two files since the
-    // key can contain all kinds of characters
-    let fn_key_mapping = "filename-key.csv";
-    let fn_perc_mapping = "filename-char_used-perc_valid.csv";
-
-    let mut key_mapping = fs::OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(format!("{DIRNAME}{MAIN_SEPARATOR}{fn_key_mapping}"))?;
-    let mut perc_mapping = fs::OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(format!("{DIRNAME}{MAIN_SEPARATOR}{fn_perc_mapping}"))?;
-
-    key_mapping.write_fmt(format_args!("file_name;key_repr{line_end}"))?;
-    perc_mapping.write_fmt(format_args!("file_name;char_used;perc_valid{line_end}"))?;
+        let filename = "tests/blank_hex.txt";
+        fs::write(filename, " \n").unwrap();
+        let file_param = Parameters::try_parse_from(["xortool", "-x", filename]).unwrap();
+        let blank = get_ciphertext(&file_param).map(|data| data.to_vec());
+        fs::write(filename, "").unwrap();
+        let empty = get_ciphertext(&file_param).map(|data| data.to_vec());
+        fs::remove_file(filename).unwrap();
+        assert!(matches!(blank, Err(XorError::UnicodeDecode { .. })));
+        assert!(matches!(empty, Err(XorError::IO { .. })));
+    }
 
-    let threshold_valid = param.threshold.unwrap_or(95);
+    #[test]
+    fn test_get_ciphertext_escaped() {
+        let param =
+            Parameters::try_parse_from(["xortool", "--escaped", "tests/escaped.txt"]).unwrap();
+        assert_eq!(
+            &*get_ciphertext(&param).unwrap(),
+            b"Hello\x00\xff\n\tWorld \xc3\xa9"
+        );
+    }
 
-    let mut count_valid = 0;
+    #[test]
+    fn test_get_ciphertext_input_range() {
+        let middle = Parameters {
+            filenames: vec![String::from("tests/small_file.txt")],
+            input_offset: Some(3),
+            input_length: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(&*get_ciphertext(&middle).unwrap(), "lo Wo".as_bytes());
 
-    for (index, key) in keys.iter().enumerate() {
-        let key_index = format!(
-            "{index:0>width$}",
-            width = format!("{}", (keys.len() - 1)).len(),
-        );
-        // FIXME: SHould be repr(key) in python
-        let key_repr = to_printable_key(key);
-        let file_name = format!("{DIRNAME}{MAIN_SEPARATOR}{key_index}.out");
+        let clamped = Parameters {
+            filenames: vec![String::from("tests/small_file.txt")],
+            input_offset: Some(6),
+            input_length: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(&*get_ciphertext(&clamped).unwrap(), "World!".as_bytes());
 
-        let dexored = dexor(ciphertext, key);
-        // ignore saving file when known plain is provided and output doesn't contain it
-        if param.known_plain.is_some()
-            && !dexored
-                .windows(param.known_plain.clone().unwrap().len())
-                .collect::<Vec<&[u8]>>()
-                .contains(&param.known_plain.clone().unwrap().as_slice())
-        {
-            continue;
-        }
-        #[expect(
-            clippy::cast_possible_truncation,
-            clippy::as_conversions,
-            reason = "Value is always between 0 and 100, so casting rounded value to i32 is safe."
-        )]
-        let perc = (100.0 * percentage_valid(&dexored, param)).round() as i32;
-        if perc > threshold_valid {
-            count_valid += 1;
-        }
-        // FIXME: write(format) vs write_fmt(format_args)
-        if key_repr.contains('\'') && !key_repr.contains("\\'") {
-            key_mapping.write_all(format!("{file_name};b\"{key_repr}\"{line_end}").as_bytes())?;
-        } else {
-            key_mapping.write_all(format!("{file_name};b'{key_repr}'{line_end}").as_bytes())?;
-        }
-        // FIXME: SHould be repr(key_char_used[key])
-        perc_mapping.write_fmt(format_args!(
-            "{file_name};{:?};{perc}{line_end}",
-            key_char_used[key]
-        ))?;
-        if !param.filter_output || (perc > threshold_valid) {
-            fs::write(file_name, dexored)?;
+        for (input_offset, input_length) in [(Some(13), None), (Some(12), None), (None, Some(0))] {
+            let empty = Parameters {
+                filenames: vec![String::from("tests/small_file.txt")],
+                input_offset,
+                input_length,
+                ..Default::default()
+            };
+            assert!(matches!(
+                get_ciphertext(&empty),
+                Err(XorError::ArgParser { .. })
+            ));
         }
     }
 
-    let mut msg = format!(
-        "Found {}{count_valid}{} plaintexts with {}{threshold_valid}{}%+ valid characters",
-        *C_COUNT, *C_RESET, *C_COUNT, *C_RESET
-    );
-    if param.known_plain.is_some() {
-        write!(
-            msg,
-            " which contained '{}'",
-            String::from_utf8(param.known_plain.clone().unwrap()).unwrap()
-        )
-        .unwrap();
+    #[test]
+    fn test_byte_histogram() {
+        let param = Parameters {
+            filenames: vec![String::from("tests/small_file.txt")],
+            ..Default::default()
+        };
+        let text = get_ciphertext(&param).unwrap();
+        assert_eq!(byte_histogram(&text, 2), vec![(b'l', 3), (b'o', 2)]);
+
+        let default_param =
+            Parameters::try_parse_from(["xortool", "--histogram", "tests/small_file.txt"]).unwrap();
+        assert_eq!(default_param.histogram, Some(10));
+        assert_eq!(default_param.filenames, vec!["tests/small_file.txt"]);
+        let top_param =
+            Parameters::try_parse_from(["xortool", "--histogram=3", "tests/small_file.txt"])
+                .unwrap();
+        assert_eq!(top_param.histogram, Some(3));
     }
-    print!("{msg}{line_end}");
-    print!("See files {fn_key_mapping}, {fn_perc_mapping}{line_end}");
 
-    Ok(())
-}
+    #[test]
+    fn test_scan_key_lengths() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let ciphertext = dexor(&plaintext, b"secret!");
+        let mut param = Parameters {
+            max_key_length: Some(65),
+            text_charset: get_charset_bytes("printable").unwrap(),
+            quiet: true,
+            ..Default::default()
+        };
+        let fitnesses = rank_key_lengths(&ciphertext, &param).unwrap();
+        let scan =
+            scan_key_lengths(&ciphertext, &ciphertext, &fitnesses, 5, b" ", &mut param).unwrap();
+        assert_eq!(scan.len(), 5);
+        let (best_length, best_valid) = scan
+            .iter()
+            .copied()
+            .reduce(|best, v| if v.1 > best.1 { v } else { best })
+            .unwrap();
+        assert_eq!(best_length, 7);
+        assert!((best_valid - 1.0).abs() < f64::EPSILON);
+        assert!(length_scan_table(&scan).contains("100.0%"));
+    }
 
-// FIXME: Make this smarter/safer?
-/// Delete the output directory if it already exists.
-fn cleanup() {
-    if fs::exists(DIRNAME).unwrap() {
-        rmdir(DIRNAME);
+    #[test]
+    fn test_calc_fitness_sum() {
+        let fitnesses = [(1, 3.2), (5, 8.3), (7, 9.3)];
+        assert_eq!(calc_fitness_sum(&fitnesses), 20.8);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_key_length_report() {
+        let fitnesses: Vec<(i32, f64)> = (1..=12).rev().map(|v| (v, f64::from(v))).collect();
+        let report = KeyLengthReport::new(&fitnesses);
+        assert_eq!(report.best, 12);
+        assert!((report.best_fitness() - 12.0).abs() < f64::EPSILON);
+        let lengths: Vec<i32> = report.candidates.iter().map(|v| v.0).collect();
+        assert_eq!(lengths, (3..=12).collect::<Vec<i32>>());
+        let pct_sum: f64 = report.candidates.iter().map(|v| v.2).sum();
+        assert!((pct_sum - 100.0).abs() < 1e-9);
+    }
 
     #[test]
-    fn test_get_ciphertext() {
+    fn test_guess_divisors_small_key_lengths() {
         let param = Parameters {
-            filename: String::from("tests/small_file.txt"),
+            max_key_length: Some(65),
             ..Default::default()
         };
-        assert_eq!(get_ciphertext(&param).unwrap(), "Hello World!".as_bytes())
+        let fitnesses = [(1, 3.2), (2, 8.3)];
+        assert_eq!(guess_and_print_divisors(&fitnesses, &param), None);
     }
 
     #[test]
-    fn test_get_ciphertext_hex() {
+    fn test_guess_divisors() {
         let param = Parameters {
-            filename: String::from("tests/small_file_hex.txt"),
-            input_is_hex: true,
+            max_key_length: Some(65),
             ..Default::default()
         };
-        assert_eq!(get_ciphertext(&param).unwrap(), "Hello World".as_bytes())
+        let fitnesses = [(8, 3.2), (16, 8.3), (24, 1.0)];
+        assert_eq!(guess_and_print_divisors(&fitnesses, &param), Some(8));
     }
 
     #[test]
-    fn test_calc_fitness_sum() {
-        let fitnesses = [(1, 3.2), (5, 8.3), (7, 9.3)];
-        assert_eq!(calc_fitness_sum(&fitnesses), 20.8);
+    fn test_guess_divisors_ties() {
+        let mut param = Parameters {
+            max_key_length: Some(130),
+            ..Default::default()
+        };
+        // Every divisor of 60 is shared by both candidates
+        let fitnesses = [(60, 3.2), (120, 8.3)];
+        let divisors = common_divisors(&fitnesses);
+        assert_eq!(divisors, vec![3, 4, 5, 6, 10, 12, 15, 20, 30, 60]);
+        assert_eq!(shown_divisors(&divisors, &param), &[3, 4, 5]);
+        assert_eq!(guess_and_print_divisors(&fitnesses, &param), Some(60));
+        param.all_divisors = true;
+        assert_eq!(shown_divisors(&divisors, &param), divisors.as_slice());
+        assert_eq!(guess_and_print_divisors(&fitnesses, &param), Some(60));
     }
 
     #[test]
-    fn test_count_equals() {
-        let text = "Hello World!".as_bytes();
-        assert_eq!(count_equals(text, 2), 1)
+    fn test_prefer_divisor_stronger_support() {
+        let fitnesses = [(8, 5.0), (16, 6.0), (24, 4.0)];
+        assert_eq!(get_max_fitnessed_key_length(&fitnesses), 16);
+        assert_eq!(prefer_divisor(&fitnesses, 16, 8), 8);
     }
 
     #[test]
-    fn test_count_equals_large_key() {
-        assert_eq!(count_equals("Hi".as_bytes(), 4), 0);
+    fn test_prefer_divisor_weaker_support() {
+        let fitnesses = [(7, 10.0), (8, 1.0), (16, 1.0)];
+        assert_eq!(prefer_divisor(&fitnesses, 7, 8), 7);
+    }
+
+    #[test]
+    fn test_prefer_divisor_tie() {
+        let fitnesses = [(8, 1.0), (16, 2.0)];
+        assert_eq!(prefer_divisor(&fitnesses, 16, 16), 16);
     }
 
     #[test]
@@ -730,6 +5080,96 @@ mod tests {
         assert_eq!(chars_count_at_offset(text, 2, 3), expected)
     }
 
+    #[test]
+    fn test_render_key_escaped() {
+        assert_eq!(
+            render_key(
+                &[b'k', 0x00, 0xde, b'y'],
+                KeyFormat::Escaped,
+                KeyLang::Python
+            ),
+            "k\\x00\\xdey".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_render_key_hex() {
+        assert_eq!(
+            render_key(&[b'k', 0x00, 0xde, b'y'], KeyFormat::Hex, KeyLang::Python),
+            "6b00de79".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_render_key_raw() {
+        assert_eq!(
+            render_key(&[b'k', 0x00, 0xde, b'y'], KeyFormat::Raw, KeyLang::Python),
+            vec![b'k', 0x00, 0xde, b'y']
+        );
+    }
+
+    #[test]
+    fn test_to_key_literal_single_quote() {
+        assert_eq!(
+            to_key_literal("it's".as_bytes(), KeyLang::Python),
+            "b\"it's\""
+        );
+    }
+
+    #[test]
+    fn test_to_key_literal_double_quote() {
+        assert_eq!(
+            to_key_literal("say \"hi\"".as_bytes(), KeyLang::Python),
+            "b'say \"hi\"'"
+        );
+    }
+
+    #[test]
+    fn test_to_key_literal_both_quotes() {
+        assert_eq!(
+            to_key_literal("'\"".as_bytes(), KeyLang::Python),
+            "b'\\'\"'"
+        );
+    }
+
+    #[test]
+    fn test_to_key_literal_backslash_before_quote() {
+        assert_eq!(
+            to_key_literal("\\\"'".as_bytes(), KeyLang::Python),
+            "b'\\\\\"\\''"
+        );
+    }
+
+    #[test]
+    fn test_to_key_literal_languages() {
+        let key = b"k\x00'\"\\\xde";
+        assert_eq!(
+            to_key_literal(key, KeyLang::Python),
+            "b'k\\x00\\'\"\\\\\\xde'"
+        );
+        assert_eq!(
+            to_key_literal(key, KeyLang::Rust),
+            "b\"k\\x00\\'\\\"\\\\\\xde\""
+        );
+        assert_eq!(
+            to_key_literal(key, KeyLang::C),
+            "\"\\x6b\\x00\\x27\\x22\\x5c\\xde\""
+        );
+        assert_eq!(
+            render_key(key, KeyFormat::Escaped, KeyLang::Rust),
+            "k\\x00\\'\\\"\\\\\\xde".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_percentage_valid_ascii() {
+        let p = Parameters {
+            text_charset: PREDEFINED_CHARSETS["ascii"].as_bytes().to_vec(),
+            ..Default::default()
+        };
+        assert!((percentage_valid(&[0x00, 0x41, 0x7F, 0x80], &p) - 0.75).abs() < 1e-9);
+    }
+
     #[test]
     fn test_percentage_valid() {
         let p = Parameters {
@@ -739,4 +5179,156 @@ mod tests {
         let text = "hela abc";
         assert_eq!(percentage_valid(text.as_bytes(), &p), 0.5)
     }
+
+    #[test]
+    fn test_percentage_valid_ignore_case() {
+        let mut p = Parameters {
+            text_charset: get_charset_bytes("a").unwrap(),
+            ..Default::default()
+        };
+        let text = b"HELLO World";
+        assert!((percentage_valid(text, &p) - 4.0 / 11.0).abs() < 1e-9);
+        p.charset_ignore_case = true;
+        assert!((percentage_valid(text, &p) - 10.0 / 11.0).abs() < 1e-9);
+        assert!((percentage_valid(b"ABCXYZ", &p) - 1.0).abs() < f64::EPSILON);
+    }
+
+    /// Guess the key length of `tests/english.txt` encrypted with `key`
+    fn guess_english_key_length(key: &[u8], score: Score) -> i32 {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let param = Parameters {
+            max_key_length: Some(65),
+            quiet: true,
+            score,
+            ..Default::default()
+        };
+        guess_key_length(&dexor(&plaintext, key), &param).unwrap().0
+    }
+
+    #[test]
+    fn test_guess_key_length_weighted() {
+        assert_eq!(guess_english_key_length(b"k3y", Score::Weighted), 3);
+        assert_eq!(guess_english_key_length(b"secret!", Score::Weighted), 7);
+        assert_eq!(
+            guess_english_key_length(b"elevenbytes", Score::Weighted),
+            11
+        );
+    }
+
+    #[test]
+    fn test_guess_key_length_agreement() {
+        assert_eq!(guess_english_key_length(b"k3y", Score::Agreement), 3);
+        assert_eq!(guess_english_key_length(b"secret!", Score::Agreement), 7);
+        assert_eq!(
+            guess_english_key_length(b"elevenbytes", Score::Agreement),
+            11
+        );
+    }
+
+    #[test]
+    fn test_guess_key_length_printable_weighted() {
+        assert_eq!(
+            guess_english_key_length(b"secret!", Score::PrintableWeighted),
+            7
+        );
+        assert_eq!(
+            guess_english_key_length(b"elevenbytes", Score::PrintableWeighted),
+            11
+        );
+    }
+
+    #[test]
+    fn test_count_printable_equals() {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let text_keyed = dexor(&plaintext, b"k3y");
+        assert_eq!(
+            count_printable_equals(&text_keyed, 3),
+            count_equals_transposed(&text_keyed, 3)
+        );
+        // No column of a binary key gives a printable key byte
+        let binary_keyed = dexor(&plaintext, &[0x80, 0x9f, 0xff]);
+        assert!(count_equals_transposed(&binary_keyed, 3) > 0);
+        assert_eq!(count_printable_equals(&binary_keyed, 3), 0);
+        assert_eq!(count_printable_equals(b"abc", 3), 0);
+    }
+
+    #[test]
+    fn test_shortest_key_length_within() {
+        let fitnesses = [(22, 0.174), (11, 0.172), (5, 0.05), (33, 0.1)];
+        assert_eq!(
+            shortest_key_length_within(&fitnesses, AGREEMENT_TOLERANCE),
+            11
+        );
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert!((comparisons(b"abcdef", 2) - 4.0).abs() < 1e-9);
+        assert!(comparisons(b"abc", 3).is_infinite());
+    }
+}
+
+/// End-to-end checks that encrypt known plaintext and recover the key
+#[cfg(test)]
+mod round_trip_tests {
+    use super::*;
+
+    /// Number of top key length candidates the true length must appear in
+    const TOP_CANDIDATES: usize = 10;
+
+    /// Encrypt `tests/english.txt` with `key` and check it can be recovered
+    ///
+    /// # Arguments
+    ///   * `key`: The key to encrypt the plaintext with
+    fn assert_round_trip(key: &[u8]) {
+        let plaintext = fs::read("tests/english.txt").unwrap();
+        let ciphertext = dexor(&plaintext, key);
+        let key_length = i32::try_from(key.len()).unwrap();
+
+        let mut param = Parameters {
+            max_key_length: Some(65),
+            quiet: true,
+            ..Default::default()
+        };
+        let mut fitnesses = calculate_fitnesses(&ciphertext, &param);
+        fitnesses.sort_by(|a, b| b.1.total_cmp(&a.1));
+        assert!(
+            fitnesses
+                .iter()
+                .take(TOP_CANDIDATES)
+                .any(|&(length, _)| length == key_length),
+            "key length {key_length} not in top candidates {fitnesses:?}"
+        );
+
+        param.known_key_length = Some(key_length);
+        let keys = guess_keys(&ciphertext, b' ', &param).unwrap();
+        assert!(
+            keys.iter().any(|guessed| guessed == key),
+            "key {key:02x?} not among guessed keys {keys:02x?}"
+        );
+        assert_eq!(dexor(&ciphertext, key), plaintext);
+    }
+
+    #[test]
+    fn test_round_trip_single_byte() {
+        assert_round_trip(b"\x42");
+    }
+
+    #[test]
+    fn test_round_trip_printable() {
+        assert_round_trip(b"k3y");
+        assert_round_trip(b"secret!");
+        assert_round_trip(b"elevenbytes");
+    }
+
+    #[test]
+    fn test_round_trip_binary() {
+        assert_round_trip(b"\x00\xff\x10\x80");
+        assert_round_trip(b"\xde\xad\xbe\xef\x01\x02\x03\x04\x05\x06\x07\x08\x09");
+    }
+
+    #[test]
+    fn test_round_trip_long_key() {
+        assert_round_trip(b"a much longer key of 29 bytes");
+    }
 }
@@ -7,14 +7,37 @@
 use std::{
     env, fs, io,
     io::{Read as _, Write as _, stdout},
-    process::exit,
+    process::ExitCode,
     vec::Vec,
 };
 
 use clap::{ArgAction, CommandFactory as _, Parser};
+use clap_complete::{Shell, generate};
 use unicode_escape::decode;
 
-use crate::error::XorError;
+use crate::{
+    cipher::{XorKey, xor},
+    error::{EXIT_ARGUMENT, EXIT_IO, XorError},
+    routine::{line_end, load_file},
+    tool_main::{BUILD_INFO, VERSION},
+};
+
+/// Size of the chunks stdin is read in when streaming
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Data given with `-f`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileInput {
+    /// Standard input, which is only read once it is needed
+    Stdin,
+    /// Contents of a file
+    Data(Vec<u8>),
+}
+
+/// Bytes of a single argument value
+///
+/// Alias so clap treats `Option<KeyBytes>` as one value instead of a list.
+pub type KeyBytes = Vec<u8>;
 
 /// Structure holding the parsed command line arguments
 #[derive(Parser, Debug)]
@@ -23,7 +46,8 @@ use crate::error::XorError;
     reason = "This structure holds CLI args, lots of bools are expected as they are for flags."
 )]
 #[command(
-    version,
+    version = VERSION,
+    long_version = BUILD_INFO,
     about,
     about = "xor strings",
     after_help = "example: xor -s lol -h 414243 -f /etc/passwd",
@@ -42,9 +66,25 @@ pub struct Parameters {
     #[arg(short='h', value_parser=from_hex_str)]
     pub hex_string: Vec<Vec<u8>>,
 
+    /// Environment variable holding a string with \\xAF escapes
+    #[arg(long, value_name = "VAR", value_parser = from_string_env)]
+    pub string_env: Vec<Vec<u8>>,
+
+    /// Environment variable holding a hex-encoded string
+    #[arg(long, value_name = "VAR", value_parser = from_hex_env)]
+    pub hex_env: Vec<Vec<u8>>,
+
     /// Read dta from file (- for stdin)
     #[arg(short='f', value_parser=from_file)]
-    pub file: Vec<Vec<u8>>,
+    pub file: Vec<FileInput>,
+
+    /// Repeating key to xor the data with (supports \\xAF escapes)
+    #[arg(short = 'k', long, value_parser=from_str)]
+    pub key: Option<KeyBytes>,
+
+    /// File holding the raw bytes of the repeating key to xor the data with
+    #[arg(long, value_name = "FILE", value_parser = from_key_file, conflicts_with = "key")]
+    pub key_file: Option<KeyBytes>,
 
     /// Newline at the end (default)
     #[arg(long="newline", action=ArgAction::SetTrue, overrides_with="no_newline")]
@@ -62,6 +102,18 @@ pub struct Parameters {
     #[arg(long="no-cycle", visible_alias ="nc", action=ArgAction::SetFalse, overrides_with="cycle")]
     pub no_cycle: bool,
 
+    /// Bitwise NOT every byte of the result
+    #[arg(long)]
+    pub invert: bool,
+
+    /// Exit with a different code for each kind of error
+    #[arg(long)]
+    pub detailed_exit_codes: bool,
+
+    /// Print a completion script for the given shell
+    #[arg(long, value_name = "SHELL", hide = true)]
+    pub completions: Option<Shell>,
+
     /// Print help
     #[clap(long, action = clap::ArgAction::HelpLong)]
     pub help: Option<bool>,
@@ -73,66 +125,240 @@ pub struct Parameters {
 ///   * `args`: Optional vector of the command line arguments to parse
 ///     If not provided, arguments are read from stdin instead.
 ///
+/// # Returns
+///   The exit code for the process
+///
 /// # Panics
 ///   Will panic if an error occurs when parsing the command line arguments.
-pub fn main(args: Option<Vec<String>>) {
+pub fn main(args: Option<Vec<String>>) -> ExitCode {
     let param = match args {
         Some(a) => Parameters::parse_from(a),
         None => Parameters::parse(),
     };
+    if let Some(shell) = param.completions {
+        generate(
+            shell,
+            &mut Parameters::command(),
+            "xortool-xor",
+            &mut io::stdout(),
+        );
+        return ExitCode::SUCCESS;
+    }
 
     let cycle = param.cycle || param.no_cycle;
     let newline = param.newline || param.no_newline;
+    let mut stdin_data = None;
+
+    if let Some((key, min_len)) = streaming_key(&param, cycle) {
+        let streamed =
+            stream_xor(io::stdin().lock(), stdout().lock(), &key, min_len).and_then(|unstreamed| {
+                match unstreamed {
+                    Some(data) => Ok(Some(data)),
+                    None if newline => stdout().write_all(b"\n").map(|()| None),
+                    None => Ok(None),
+                }
+            });
+        match streamed {
+            Ok(None) => return ExitCode::SUCCESS,
+            // Input was too short to stream, so it is xored in memory below
+            Ok(Some(data)) => stdin_data = Some(data),
+            Err(e) => return io_error_exit(e, param.detailed_exit_codes),
+        }
+    }
 
     let mut datas = Vec::new();
     datas.extend_from_slice(&param.string);
     datas.extend_from_slice(&param.raw_string);
     datas.extend_from_slice(&param.hex_string);
-    datas.extend_from_slice(&param.file);
+    datas.extend_from_slice(&param.string_env);
+    datas.extend_from_slice(&param.hex_env);
+    for file in &param.file {
+        match file {
+            FileInput::Data(data) => datas.push(data.clone()),
+            FileInput::Stdin => {
+                let data = if let Some(data) = stdin_data.take() {
+                    data
+                } else {
+                    let mut buf = Vec::new();
+                    if let Err(e) = io::stdin().read_to_end(&mut buf) {
+                        return io_error_exit(e, param.detailed_exit_codes);
+                    }
+                    buf
+                };
+                datas.push(data);
+            }
+        }
+    }
 
     if datas.is_empty() {
-        let line_end = if env::consts::OS == "windows" {
-            "\r\n"
-        } else {
-            "\n"
-        };
+        let line_end = line_end();
         eprint!("error: no data given{line_end}{line_end}");
         eprint!("{}{line_end}", Parameters::command().render_help());
-        exit(1)
+        return if param.detailed_exit_codes {
+            ExitCode::from(EXIT_ARGUMENT)
+        } else {
+            ExitCode::FAILURE
+        };
     }
 
-    let result = xor(datas, cycle);
-    // FIXME: Replace these unwraps with conversion to XorError::IO
-    stdout().write_all(&result).unwrap();
-    if newline {
-        stdout().write_all("\n".as_bytes()).unwrap();
+    let mut result = xor(datas, cycle);
+    if let Some(key) = repeating_key(&param) {
+        XorKey::from(key.as_slice()).cycle_xor_in_place(&mut result);
     }
-    stdout().flush().unwrap();
+    if param.invert {
+        invert(&mut result);
+    }
+    if let Err(e) = write_result(&result, newline) {
+        return io_error_exit(e, param.detailed_exit_codes);
+    }
+    ExitCode::SUCCESS
 }
 
-/// Compute xor-encoded value of all of the data
+/// Print an IO error and get the exit code for it
 ///
 /// # Arguments
-///   * `args`: 2D Vector of all of the data to xor encode
-///   * `cycle`: Whether to use the longest of the data components for
-///     iteration length (true), or the length of each individual component (false)
+///   * `e`: The error that occurred
+///   * `detailed_exit_codes`: Whether to use the detailed exit code
 ///
 /// # Returns
-///  xor-encoding of all of the data
-fn xor(mut args: Vec<Vec<u8>>, cycle: bool) -> Vec<u8> {
-    args.sort_by_key(Vec::len);
-    // Pop First then reverse is the same as popping first item after reversing
-    let mut res = args.pop().unwrap();
-    args.reverse();
-    let maxlen = res.len();
-    for s in args {
-        let slen = s.len();
-        let range_end = if cycle { maxlen } else { slen };
-        for i in 0..range_end {
-            res[i] ^= s[i % slen];
+///   The exit code to exit with
+fn io_error_exit(e: io::Error, detailed_exit_codes: bool) -> ExitCode {
+    eprint!("{}{}", XorError::from(e), line_end());
+    if detailed_exit_codes {
+        ExitCode::from(EXIT_IO)
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Get the key to stream stdin past, if the inputs allow streaming
+///
+/// Streaming is possible when stdin is the only `-f` input and there is a
+/// single repeating key: either `--key` with no other data, or one other
+/// input when cycling. With `--invert` the key is inverted instead of the
+/// result, since `!(a ^ k) == a ^ !k`.
+///
+/// # Arguments
+///   * `param`: The parsed command line arguments
+///   * `cycle`: Whether shorter inputs are cycled over the longest
+///
+/// # Returns
+///   The key, and the length stdin must exceed to be streamed, or `None`
+///   if the inputs need to be xored in memory
+fn streaming_key(param: &Parameters, cycle: bool) -> Option<(XorKey, usize)> {
+    if param.file != [FileInput::Stdin] {
+        return None;
+    }
+    let mut others = param
+        .string
+        .iter()
+        .chain(&param.raw_string)
+        .chain(&param.hex_string)
+        .chain(&param.string_env)
+        .chain(&param.hex_env);
+    let (mut key, min_len) = match (repeating_key(param), others.next(), others.next()) {
+        // The key is cycled over the data whatever their lengths
+        (Some(key), None, None) => (key.clone(), 0),
+        // The shorter input is cycled, so stdin must be the longer one
+        (None, Some(other), None) if cycle => (other.clone(), other.len()),
+        _ => return None,
+    };
+    if param.invert {
+        if key.is_empty() {
+            return None;
         }
+        invert(&mut key);
     }
-    res
+    Some((XorKey::from(key), min_len))
+}
+
+/// Get the repeating key given with `--key` or `--key-file`
+///
+/// # Arguments
+///   * `param`: The parsed command line arguments
+///
+/// # Returns
+///   The key bytes, or `None` if no key was given
+fn repeating_key(param: &Parameters) -> Option<&KeyBytes> {
+    param.key.as_ref().or(param.key_file.as_ref())
+}
+
+/// Bitwise NOT every byte of the data
+///
+/// # Arguments
+///   * `data`: The data to invert in place
+fn invert(data: &mut [u8]) {
+    for byte in data {
+        *byte = !*byte;
+    }
+}
+
+/// Xor an input stream with a repeating key, writing the output as it goes
+///
+/// # Arguments
+///   * `input`: The data to xor
+///   * `output`: Where to write the xored data
+///   * `key`: The repeating key
+///   * `min_len`: The input is only streamed if it is longer than this
+///
+/// # Returns
+///   `None` if the input was streamed, or all of the input if it was too
+///   short to stream (nothing is written in that case)
+///
+/// # Errors
+///   Returns any error from reading the input or writing the output
+fn stream_xor(
+    mut input: impl io::Read,
+    mut output: impl io::Write,
+    key: &XorKey,
+    min_len: usize,
+) -> io::Result<Option<Vec<u8>>> {
+    let mut buf = Vec::new();
+    while buf.len() <= min_len {
+        if input
+            .by_ref()
+            .take(u64::try_from(STREAM_CHUNK_SIZE).unwrap_or(u64::MAX))
+            .read_to_end(&mut buf)?
+            == 0
+        {
+            return Ok(Some(buf));
+        }
+    }
+    key.cycle_xor_in_place(&mut buf);
+    output.write_all(&buf)?;
+
+    let mut position = buf.len();
+    buf.resize(STREAM_CHUNK_SIZE, 0);
+    loop {
+        let read = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        key.cycle_xor_in_place_at(&mut buf[..read], position);
+        output.write_all(&buf[..read])?;
+        position += read;
+    }
+    output.flush()?;
+    Ok(None)
+}
+
+/// Write the xored data to standard output
+///
+/// # Arguments
+///   * `result`: The xored data
+///   * `newline`: Whether to add a newline at the end
+///
+/// # Errors
+///   Returns any error from writing to standard output
+fn write_result(result: &[u8], newline: bool) -> io::Result<()> {
+    let mut out = stdout().lock();
+    out.write_all(result)?;
+    if newline {
+        out.write_all("\n".as_bytes())?;
+    }
+    out.flush()
 }
 
 /// Convert a string into a vector of bytes, decoding escape sequences
@@ -187,21 +413,70 @@ fn from_raw_str(arg: &str) -> Result<Vec<u8>, XorError> {
 ///   Hex characters converted to bytes
 ///
 /// # Errors
-///   Returns `XorError::ArgParser` if the supplied string is empty
+///   Returns `XorError::ArgParser` if the supplied string is empty or has
+///   characters other than hex digits and spaces
 fn from_hex_str(arg: &str) -> Result<Vec<u8>, XorError> {
     if arg.is_empty() {
-        Err(XorError::ArgParser {
+        return Err(XorError::ArgParser {
             msg: "Empty String".to_owned(),
-        })
-    } else {
-        Ok(arg
-            .replace(' ', "")
-            .chars()
-            .collect::<Vec<char>>()
-            .chunks(2)
-            .map(|c| u8::from_str_radix(&c.iter().collect::<String>(), 16).unwrap())
-            .collect())
+        });
     }
+    let digits = arg.replace(' ', "");
+    if let Some(bad) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(XorError::ArgParser {
+            msg: format!("Invalid hex digit {bad:?} in '{arg}'"),
+        });
+    }
+    // Only ASCII hex digits are left, so every pair is a whole byte
+    Ok(digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(str::from_utf8(pair).unwrap(), 16).unwrap())
+        .collect())
+}
+
+/// Read the value of an environment variable
+///
+/// # Arguments
+///   * `var`: The name of the variable
+///
+/// # Returns
+///   The value of the variable
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if the variable is unset or not unicode
+fn read_env(var: &str) -> Result<String, XorError> {
+    env::var(var).map_err(|e| XorError::ArgParser {
+        msg: format!("Environment variable {var}: {e}"),
+    })
+}
+
+/// Read a string with escapes from an environment variable
+///
+/// # Arguments
+///   * `var`: The name of the variable
+///
+/// # Returns
+///   The bytes of the string, with escape sequences decoded
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if the variable is unset or empty
+fn from_string_env(var: &str) -> Result<Vec<u8>, XorError> {
+    from_str(&read_env(var)?)
+}
+
+/// Read a hex-encoded string from an environment variable
+///
+/// # Arguments
+///   * `var`: The name of the variable
+///
+/// # Returns
+///   Hex characters converted to bytes
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if the variable is unset, empty, or not hex
+fn from_hex_env(var: &str) -> Result<Vec<u8>, XorError> {
+    from_hex_str(&read_env(var)?)
 }
 
 /// Read from a file into a vector of bytes
@@ -210,23 +485,55 @@ fn from_hex_str(arg: &str) -> Result<Vec<u8>, XorError> {
 ///   * `file`: The file to read from. If "-", will read from stdin instead
 ///
 /// # Returns
-///   Vector of the bytes that were read in.
+///   The bytes that were read in. Standard input is not read yet, so
+///   it can be streamed.
 ///
 /// # Errors
-///   Returns `XorError::ArgParser` if the supplied string is empty
-fn from_file(s: &str) -> Result<Vec<u8>, XorError> {
+///   Returns `XorError::ArgParser` if the supplied string is empty, names
+///   a directory, or names a file that can't be read
+fn from_file(s: &str) -> Result<FileInput, XorError> {
     if s.is_empty() {
         Err(XorError::ArgParser {
             msg: "Empty String".to_owned(),
         })
     } else {
         if s == "-" {
-            let mut buf = Vec::new();
-            io::stdin().read_to_end(&mut buf)?;
-            return Ok(buf);
+            return Ok(FileInput::Stdin);
+        }
+        // Pipes and devices (e.g. `-f <(cmd)`) are fine, only directories are rejected
+        if fs::metadata(s).is_ok_and(|metadata| metadata.is_dir()) {
+            return Err(XorError::ArgParser {
+                msg: format!("-f {s}: Is a directory, expected a file"),
+            });
         }
-        Ok(fs::read(s)?)
+        fs::read(s)
+            .map(FileInput::Data)
+            .map_err(|e| XorError::ArgParser {
+                msg: format!("-f {s}: {e}"),
+            })
+    }
+}
+
+/// Read the raw bytes of a key from a file
+///
+/// # Arguments
+///   * `s`: The file to read from. If "-", will read from stdin instead
+///
+/// # Returns
+///   The bytes of the file, unchanged
+///
+/// # Errors
+///   Returns `XorError::ArgParser` if the file can't be read or is empty
+fn from_key_file(s: &str) -> Result<KeyBytes, XorError> {
+    let key = load_file(s).map_err(|e| XorError::ArgParser {
+        msg: format!("--key-file {s}: {e}"),
+    })?;
+    if key.is_empty() {
+        return Err(XorError::ArgParser {
+            msg: format!("--key-file {s}: The key file is empty"),
+        });
     }
+    Ok(key)
 }
 
 #[cfg(test)]
@@ -255,13 +562,239 @@ mod tests {
             from_hex_str("48 65 6C 6C 6F 20 57 6F 72 6C 64"),
             Ok("Hello World".as_bytes().to_vec())
         );
+        for bad in ["4g", "+f", "48 65 zz", "\u{e9}"] {
+            assert!(matches!(from_hex_str(bad), Err(XorError::ArgParser { .. })));
+        }
     }
 
     #[test]
     fn test_from_file() {
         assert_eq!(
             from_file("tests/small_file.txt"),
-            Ok("Hello World!".as_bytes().to_vec())
+            Ok(FileInput::Data("Hello World!".as_bytes().to_vec()))
         );
     }
+
+    #[test]
+    fn test_completions() {
+        let param = Parameters::try_parse_from(["xortool-xor", "--completions", "bash"]).unwrap();
+        let mut script = Vec::new();
+        generate(
+            param.completions.unwrap(),
+            &mut Parameters::command(),
+            "xortool-xor",
+            &mut script,
+        );
+        assert!(String::from_utf8(script).unwrap().contains("xortool-xor"));
+    }
+
+    #[test]
+    fn test_key_shorter_than_data() {
+        let result = XorKey::from(b"ab".as_slice()).cycle_xor(b"Hello World");
+        assert_eq!(
+            result,
+            xor(vec![b"Hello World".to_vec(), b"ab".to_vec()], true)
+        );
+    }
+
+    #[test]
+    fn test_key_longer_than_data() {
+        // Only the start of the key is used, the data length is kept
+        let result = XorKey::from(b"secret".as_slice()).cycle_xor(b"Hi");
+        assert_eq!(result, vec![b'H' ^ b's', b'i' ^ b'e']);
+    }
+
+    #[test]
+    fn test_key_arg() {
+        let param =
+            Parameters::try_parse_from(["xortool-xor", "-r", "Hello", "--key", "\\x01"]).unwrap();
+        assert_eq!(param.key, Some(vec![1]));
+        assert_eq!(param.raw_string, vec![b"Hello".to_vec()]);
+    }
+
+    #[test]
+    fn test_key_file_arg() {
+        let param = Parameters::try_parse_from([
+            "xortool-xor",
+            "-r",
+            "Hello",
+            "--key-file",
+            "tests/null_key.bin",
+        ])
+        .unwrap();
+        let key = repeating_key(&param).unwrap();
+        assert_eq!(key, b"k\x00y\x00");
+        let mut result = xor(param.raw_string.clone(), true);
+        XorKey::from(key.as_slice()).cycle_xor_in_place(&mut result);
+        assert_eq!(result, b"#e\x15l\x04");
+
+        // --key and --key-file conflict
+        Parameters::try_parse_from([
+            "xortool-xor",
+            "-r",
+            "Hello",
+            "--key",
+            "k",
+            "--key-file",
+            "tests/null_key.bin",
+        ])
+        .unwrap_err();
+
+        let filename = "tests/empty_key.bin";
+        fs::write(filename, b"").unwrap();
+        let empty = from_key_file(filename);
+        fs::remove_file(filename).unwrap();
+        assert!(matches!(empty, Err(XorError::ArgParser { .. })));
+    }
+
+    #[test]
+    fn test_long_version() {
+        let version = Parameters::command().render_long_version();
+        assert!(version.contains(VERSION));
+        assert!(version.contains("target: "));
+        assert!(version.contains("features: "));
+        // -h is taken by hex strings, so help is only on --help
+        assert!(
+            Parameters::command()
+                .get_arguments()
+                .any(|arg| arg.get_id() == "help")
+        );
+    }
+
+    #[test]
+    fn test_main_exit_codes() {
+        assert_eq!(
+            main(Some(vec![String::from("xortool-xor"), String::from("-n")])),
+            ExitCode::FAILURE
+        );
+        assert_eq!(
+            main(Some(vec![
+                String::from("xortool-xor"),
+                String::from("--detailed-exit-codes")
+            ])),
+            ExitCode::from(EXIT_ARGUMENT)
+        );
+    }
+
+    #[test]
+    fn test_from_file_stdin() {
+        assert_eq!(from_file("-"), Ok(FileInput::Stdin));
+    }
+
+    #[test]
+    fn test_stream_xor_large_input() {
+        let data: Vec<u8> = (0..=255).cycle().take(STREAM_CHUNK_SIZE * 3 + 17).collect();
+        let key = XorKey::from(b"k3y!".as_slice());
+        let mut output = Vec::new();
+        let unstreamed = stream_xor(data.as_slice(), &mut output, &key, 4).unwrap();
+        assert_eq!(unstreamed, None);
+        assert_eq!(output, xor(vec![data, b"k3y!".to_vec()], true));
+    }
+
+    #[test]
+    fn test_stream_xor_short_input() {
+        let key = XorKey::from(b"longer key".as_slice());
+        let mut output = Vec::new();
+        let unstreamed = stream_xor(b"short".as_slice(), &mut output, &key, 10).unwrap();
+        assert_eq!(unstreamed, Some(b"short".to_vec()));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_streaming_key() {
+        let parse = |args: &[&str]| Parameters::try_parse_from(args).unwrap();
+        let key_param = parse(&["xortool-xor", "-f", "-", "--key", "ab"]);
+        assert_eq!(
+            streaming_key(&key_param, true),
+            Some((XorKey::from(b"ab".as_slice()), 0))
+        );
+        let input_param = parse(&["xortool-xor", "-f", "-", "-r", "abc"]);
+        assert_eq!(
+            streaming_key(&input_param, true),
+            Some((XorKey::from(b"abc".as_slice()), 3))
+        );
+        assert_eq!(streaming_key(&input_param, false), None);
+        let two_param = parse(&["xortool-xor", "-f", "-", "-r", "abc", "-r", "def"]);
+        assert_eq!(streaming_key(&two_param, true), None);
+    }
+
+    #[test]
+    fn test_invert() {
+        let mut result = xor(vec![b"Hi".to_vec(), vec![0x0F, 0xF0]], true);
+        assert_eq!(result, vec![0x47, 0x99]);
+        invert(&mut result);
+        assert_eq!(result, vec![0xB8, 0x66]);
+
+        let param =
+            Parameters::try_parse_from(["xortool-xor", "-f", "-", "--key", "\\x0f", "--invert"])
+                .unwrap();
+        assert_eq!(
+            streaming_key(&param, true),
+            Some((XorKey::from(vec![0xF0]), 0))
+        );
+    }
+
+    /// Set an environment variable that only one test uses
+    fn set_test_env(var: &str, value: &str) {
+        // SAFETY: Each variable is only used by one test, and nothing outside
+        // of std reads the environment while the tests run.
+        unsafe { env::set_var(var, value) }
+    }
+
+    #[test]
+    fn test_env_inputs() {
+        set_test_env("XORTOOL_TEST_ENV_STRING", "Hello\\x01");
+        set_test_env("XORTOOL_TEST_ENV_HEX", "2020");
+        let param = Parameters::try_parse_from([
+            "xortool-xor",
+            "--string-env",
+            "XORTOOL_TEST_ENV_STRING",
+            "--hex-env",
+            "XORTOOL_TEST_ENV_HEX",
+        ])
+        .unwrap();
+        assert_eq!(param.string_env, vec![b"Hello\x01".to_vec()]);
+        assert_eq!(param.hex_env, vec![vec![0x20, 0x20]]);
+        assert_eq!(
+            xor([param.string_env, param.hex_env].concat(), true),
+            b"hELLO!"
+        );
+    }
+
+    #[test]
+    fn test_env_input_unset() {
+        assert!(matches!(
+            from_string_env("XORTOOL_TEST_ENV_UNSET"),
+            Err(XorError::ArgParser { .. })
+        ));
+        Parameters::try_parse_from(["xortool-xor", "--hex-env", "XORTOOL_TEST_ENV_UNSET"])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_hex_env_bad_value() {
+        set_test_env("XORTOOL_TEST_ENV_BAD_HEX", "20xy");
+        assert!(matches!(
+            from_hex_env("XORTOOL_TEST_ENV_BAD_HEX"),
+            Err(XorError::ArgParser { .. })
+        ));
+        Parameters::try_parse_from(["xortool-xor", "--hex-env", "XORTOOL_TEST_ENV_BAD_HEX"])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_from_file_directory() {
+        assert!(matches!(
+            from_file("tests"),
+            Err(XorError::ArgParser { msg }) if msg.starts_with("-f tests:")
+        ));
+    }
+
+    #[test]
+    fn test_from_file_missing() {
+        assert!(matches!(
+            from_file("tests/does_not_exist.txt"),
+            Err(XorError::ArgParser { msg }) if msg.starts_with("-f tests/does_not_exist.txt:")
+        ));
+    }
 }
@@ -0,0 +1,64 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Check that `--print0` keeps the whole of stdout for the keys
+#![expect(
+    clippy::tests_outside_test_module,
+    reason = "Integration tests are a crate of their own that is only built for testing."
+)]
+use std::{
+    env, fs,
+    process::{self, Command},
+};
+
+/// Run xortool with `--print0` on encrypted English text
+///
+/// The run is done in a fresh directory, since the output directory is
+/// created (and cleaned up) in the working directory.
+///
+/// # Arguments
+///   * `name`: Name for the run's directory
+///   * `extra_args`: Arguments to add to the command line
+///
+/// # Returns
+///   Everything xortool printed to stdout and stderr
+fn run_print0(name: &str, extra_args: &[&str]) -> (Vec<u8>, Vec<u8>) {
+    let work_dir = env::temp_dir().join(format!("xortool_{name}_{}", process::id()));
+    fs::create_dir_all(&work_dir).unwrap();
+    let plaintext = fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/english.txt")).unwrap();
+    let ciphertext: Vec<u8> = plaintext
+        .iter()
+        .zip(b"secret!".iter().cycle())
+        .map(|(byte, key)| byte ^ key)
+        .collect();
+    fs::write(work_dir.join("enc.bin"), ciphertext).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_xortool"))
+        .args(["enc.bin", "-c", "20", "--print0"])
+        .args(extra_args)
+        .current_dir(&work_dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&work_dir).unwrap();
+    assert!(output.status.success(), "xortool failed: {output:?}");
+    (output.stdout, output.stderr)
+}
+
+#[test]
+fn test_print0_stdout_is_only_keys() {
+    let (stdout, stderr) = run_print0("print0", &[]);
+    assert_eq!(stdout, b"secret!\0");
+    // The usual messages still go to stderr
+    let messages = String::from_utf8_lossy(&stderr);
+    assert!(messages.contains("The most probable key lengths:"));
+    assert!(messages.contains("plaintexts with"));
+}
+
+#[test]
+fn test_print0_quiet_stdout_is_only_keys() {
+    let (stdout, stderr) = run_print0("print0_quiet", &["-q"]);
+    assert_eq!(stdout, b"secret!\0");
+    assert!(String::from_utf8_lossy(&stderr).contains("plaintexts with"));
+}